@@ -0,0 +1,347 @@
+//! Component model binary format.
+//!
+//! See https://github.com/WebAssembly/component-model/blob/main/design/mvp/Binary.md
+
+use crate::{
+    wasm_grammar, Decode, DecodeContext, DecodeError, DecodeResult, Encode, EncodeDyn, Name,
+    Repeated, Sized,
+};
+use crate::{
+    Custom, FuncIdx as CoreFuncIdx, Import as CoreImport, ImportDesc as CoreImportDesc,
+    MemIdx as CoreMemIdx, Module as CoreModule, RecType, TypeIdx as CoreTypeIdx,
+};
+
+use crate::io::{self, Write};
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+wasm_grammar! {
+    // Core module types
+    extern Custom;
+    extern CoreModule;
+    // `0x50` (`sub`) is left out: in core type definitions it's the prefix of module types
+    extern RecType: 0x4E | 0x4F | 0x5E | 0x5F | 0x60;
+    extern CoreImport;
+    extern CoreImportDesc;
+    extern CoreFuncIdx;
+    extern CoreTypeIdx;
+    extern CoreMemIdx;
+
+    Component {
+        0x00 0x61 0x73 0x6D // magic
+        0x0D 0x00 // version
+        0x01 0x00 // layer
+        sections:repeated(Section) = Component,
+    }
+
+    Section {
+        // Custom section
+        0x00 custom:sized(Custom) = Custom,
+
+        // Core module section
+        0x01 m:sized(CoreModule) = CoreModule,
+
+        // Core instance section
+        0x02 is:sized(vec(CoreInstance)) = CoreInstance,
+
+        // Core type section
+        0x03 ts:sized(vec(CoreType)) = CoreType,
+
+        // Component section
+        0x04 c:sized(Component) = Component,
+
+        // Instance section
+        0x05 is:sized(vec(Instance)) = Instance,
+
+        // Alias section
+        0x06 as_:sized(vec(Alias)) = Alias,
+
+        // Type section
+        0x07 ts:sized(vec(Type)) = Type,
+
+        // Canon section
+        0x08 cs:sized(vec(Canon)) = Canon,
+
+        // Start section
+        0x09 s:sized(Start) = Start,
+
+        // Import section
+        0x0A is:sized(vec(Import)) = Import,
+
+        // Export section
+        0x0B es:sized(vec(Export)) = Export,
+    }
+
+    //
+    // Core instance section
+    //
+
+    CoreInstance {
+        0x00 m:CoreModuleIdx args:vec(CoreInstantiateArg) = Instantiate,
+        0x01 es:vec(CoreInlineExport) = FromExports,
+    }
+
+    CoreInstantiateArg {
+        n:name 0x12 i:CoreInstanceIdx = CoreInstantiateArg,
+    }
+
+    CoreInlineExport {
+        n:name si:CoreSortIdx = CoreInlineExport,
+    }
+
+    CoreSortIdx {
+        sort:CoreSort idx:u32 = CoreSortIdx,
+    }
+
+    CoreSort {
+        0x00 = Func,
+        0x01 = Table,
+        0x02 = Memory,
+        0x03 = Global,
+        0x10 = Type,
+        0x11 = Module,
+        0x12 = Instance,
+    }
+
+    //
+    // Core type section
+    //
+
+    CoreType {
+        0x50 decls:vec(ModuleDecl) = Module,
+        rt:RecType = Rec,
+    }
+
+    ModuleDecl {
+        0x00 i:CoreImport = Import,
+        0x01 t:CoreType = Type,
+        0x02 a:CoreAlias = Alias,
+        0x03 e:CoreExportDecl = Export,
+    }
+
+    CoreAlias {
+        sort:CoreSort 0x01 ct:u32 idx:u32 = CoreAlias,
+    }
+
+    CoreExportDecl {
+        n:name d:CoreImportDesc = CoreExportDecl,
+    }
+
+    //
+    // Instance section
+    //
+
+    Instance {
+        0x00 c:ComponentIdx args:vec(InstantiateArg) = Instantiate,
+        0x01 es:vec(InlineExport) = FromExports,
+    }
+
+    InstantiateArg {
+        n:name si:SortIdx = InstantiateArg,
+    }
+
+    InlineExport {
+        n:ExternName si:SortIdx = InlineExport,
+    }
+
+    Sort {
+        0x00 cs:CoreSort = Core,
+        0x01 = Func,
+        0x02 = Value,
+        0x03 = Type,
+        0x04 = Component,
+        0x05 = Instance,
+    }
+
+    SortIdx {
+        sort:Sort idx:u32 = SortIdx,
+    }
+
+    //
+    // Alias section
+    //
+
+    Alias {
+        sort:Sort t:AliasTarget = Alias,
+    }
+
+    AliasTarget {
+        0x00 i:InstanceIdx n:name = Export,
+        0x01 i:CoreInstanceIdx n:name = CoreExport,
+        0x02 ct:u32 idx:u32 = Outer,
+    }
+
+    //
+    // Type section
+    //
+
+    Type {
+        0x40 ps:vec(LabelValType) rs:ResultList = Func,
+        0x41 ds:vec(ComponentDecl) = Component,
+        0x42 ds:vec(InstanceDecl) = Instance,
+        0x3F 0x7F dtor:optional(CoreFuncIdx) = Resource,
+        dvt:DefValType = DefVal,
+    }
+
+    DefValType {
+        0x72 lts:vec(LabelValType) = Record,
+        0x71 cases:vec(Case) = Variant,
+        0x70 t:ValType = List,
+        0x6F ts:vec(ValType) = Tuple,
+        0x6E ls:vec(Name) = Flags,
+        0x6D ls:vec(Name) = Enum,
+        0x6B t:ValType = Option,
+        0x6A t:optional(ValType) u:optional(ValType) = Result,
+        0x69 i:TypeIdx = Own,
+        0x68 i:TypeIdx = Borrow,
+        pvt:PrimValType = Prim,
+    }
+
+    PrimValType {
+        0x7F = Bool,
+        0x7E = S8,
+        0x7D = U8,
+        0x7C = S16,
+        0x7B = U16,
+        0x7A = S32,
+        0x79 = U32,
+        0x78 = S64,
+        0x77 = U64,
+        0x76 = F32,
+        0x75 = F64,
+        0x74 = Char,
+        0x73 = String,
+        0x64 = ErrorContext,
+    }
+
+    ValType {
+        pvt:PrimValType = Prim,
+        i:s33_index = Type,
+    }
+
+    LabelValType {
+        l:name t:ValType = LabelValType,
+    }
+
+    Case {
+        l:name t:optional(ValType) 0x00 = Case,
+    }
+
+    ResultList {
+        0x00 t:ValType = Result,
+        0x01 0x00 = Empty,
+    }
+
+    ComponentDecl {
+        0x03 i:Import = Import,
+        d:InstanceDecl = Instance,
+    }
+
+    InstanceDecl {
+        0x00 t:CoreType = CoreType,
+        0x01 t:Type = Type,
+        0x02 a:Alias = Alias,
+        0x04 e:ExportDecl = Export,
+    }
+
+    ExportDecl {
+        n:ExternName ed:ExternDesc = ExportDecl,
+    }
+
+    ExternDesc {
+        0x00 0x11 i:CoreTypeIdx = CoreModule,
+        0x01 i:TypeIdx = Func,
+        0x02 b:ValueBound = Value,
+        0x03 b:TypeBound = Type,
+        0x04 i:TypeIdx = Component,
+        0x05 i:TypeIdx = Instance,
+    }
+
+    ValueBound {
+        0x00 i:ValueIdx = Eq,
+        0x01 t:ValType = Type,
+    }
+
+    TypeBound {
+        0x00 i:TypeIdx = Eq,
+        0x01 = SubResource,
+    }
+
+    //
+    // Canon section
+    //
+
+    Canon {
+        0x00 0x00 f:CoreFuncIdx opts:vec(CanonOpt) ft:TypeIdx = Lift,
+        0x01 0x00 f:FuncIdx opts:vec(CanonOpt) = Lower,
+        0x02 rt:TypeIdx = ResourceNew,
+        0x03 rt:TypeIdx = ResourceDrop,
+        0x04 rt:TypeIdx = ResourceRep,
+    }
+
+    CanonOpt {
+        0x00 = Utf8,
+        0x01 = Utf16,
+        0x02 = CompactUtf16,
+        0x03 m:CoreMemIdx = Memory,
+        0x04 f:CoreFuncIdx = Realloc,
+        0x05 f:CoreFuncIdx = PostReturn,
+    }
+
+    //
+    // Start section
+    //
+
+    Start {
+        f:FuncIdx args:vec(ValueIdx) r:u32 = Start,
+    }
+
+    //
+    // Import and export sections
+    //
+
+    Import {
+        n:ExternName ed:ExternDesc = Import,
+    }
+
+    Export {
+        n:ExternName si:SortIdx ed:optional(ExternDesc) = Export,
+    }
+
+    ExternName {
+        0x00 n:name = ExternName,
+    }
+
+    //
+    // Indices
+    //
+
+    CoreModuleIdx {
+        x:u32 = CoreModuleIdx,
+    }
+
+    CoreInstanceIdx {
+        x:u32 = CoreInstanceIdx,
+    }
+
+    FuncIdx {
+        x:u32 = FuncIdx,
+    }
+
+    ValueIdx {
+        x:u32 = ValueIdx,
+    }
+
+    TypeIdx {
+        x:u32 = TypeIdx,
+    }
+
+    InstanceIdx {
+        x:u32 = InstanceIdx,
+    }
+
+    ComponentIdx {
+        x:u32 = ComponentIdx,
+    }
+}
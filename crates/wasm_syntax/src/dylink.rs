@@ -0,0 +1,62 @@
+//! The `dylink.0` custom section of dynamic libraries ("side modules").
+//!
+//! See https://github.com/WebAssembly/tool-conventions/blob/main/DynamicLinking.md
+
+use crate::{
+    wasm_grammar, Decode, DecodeContext, DecodeError, DecodeResult, Encode, Name, Repeated, Sized,
+};
+
+use crate::io::{self, Write};
+
+use alloc::vec::Vec;
+
+/// Name of the custom section with the dynamic linking metadata. This should be the first section
+/// of the module.
+pub const DYLINK_SECTION_NAME: &str = "dylink.0";
+
+wasm_grammar! {
+    Dylink {
+        subsections:repeated(DylinkSubsection) = Dylink,
+    }
+
+    DylinkSubsection {
+        0x01 mem_info:sized(MemInfo) = MemInfo,
+        0x02 needed:sized(vec(Name)) = Needed,
+        0x03 exports:sized(vec(ExportInfo)) = ExportInfo,
+        0x04 imports:sized(vec(ImportInfo)) = ImportInfo,
+        0x05 paths:sized(vec(Name)) = RuntimePath,
+    }
+
+    MemInfo {
+        memory_size:u32 memory_align:u32 table_size:u32 table_align:u32 = MemInfo,
+    }
+
+    ExportInfo {
+        name:name flags:u32 = ExportInfo,
+    }
+
+    ImportInfo {
+        module:name field:name flags:u32 = ImportInfo,
+    }
+}
+
+impl Dylink {
+    /// The memory and table requirements of the module, if the section has them.
+    pub fn mem_info(&self) -> Option<&MemInfo> {
+        (self.0).0.iter().find_map(|subsection| match subsection {
+            DylinkSubsection::MemInfo(Sized(mem_info)) => Some(mem_info),
+            _ => None,
+        })
+    }
+
+    /// Mutable version of [`Dylink::mem_info`].
+    pub fn mem_info_mut(&mut self) -> Option<&mut MemInfo> {
+        (self.0)
+            .0
+            .iter_mut()
+            .find_map(|subsection| match subsection {
+                DylinkSubsection::MemInfo(Sized(mem_info)) => Some(mem_info),
+                _ => None,
+            })
+    }
+}
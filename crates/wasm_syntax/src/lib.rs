@@ -1,21 +1,300 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 use wasm_syntax_gen::grammar as wasm_grammar;
 
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use io::Write;
+
+#[cfg(feature = "std")]
+use std::io::Read;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+pub mod component;
+pub mod dylink;
+pub mod io;
+pub mod linking;
+pub mod operators;
+
 #[cfg(test)]
 mod test;
 
 pub trait Encode {
-    fn encode(&self, buffer: &mut Vec<u8>);
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+
+    /// Number of bytes `encode_to` writes.
+    fn encoded_len(&self) -> usize;
+
+    /// Appends the encoding to `buffer`.
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        // Writing to a `Vec` does not fail
+        self.encode_to(buffer).unwrap()
+    }
+}
+
+/// Object-safe version of [`Encode`], used by the generated encoders of recursive types.
+pub(crate) trait EncodeDyn {
+    fn encode_dyn(&self, writer: &mut dyn Write) -> io::Result<()>;
+
+    fn encoded_len_dyn(&self) -> usize;
 }
 
-pub trait Decode: std::marker::Sized {
-    fn decode(buffer: &'_ [u8]) -> DecodeResult<'_, Self>;
+impl<T: Encode> EncodeDyn for T {
+    fn encode_dyn(&self, mut writer: &mut dyn Write) -> io::Result<()> {
+        self.encode_to(&mut writer)
+    }
+
+    fn encoded_len_dyn(&self) -> usize {
+        self.encoded_len()
+    }
+}
+
+/// Size of the unsigned LEB128 encoding of a value with `bits` significant bits.
+fn leb128_len(bits: u32) -> usize {
+    core::cmp::max(1, (bits as usize).div_ceil(7))
+}
+
+pub trait Decode: core::marker::Sized {
+    /// Decode with the default limits.
+    fn decode(buffer: &'_ [u8]) -> DecodeResult<'_, Self> {
+        Self::decode_with(buffer, &mut DecodeContext::default())
+    }
+
+    fn decode_with<'a>(buffer: &'a [u8], ctx: &mut DecodeContext) -> DecodeResult<'a, Self>;
+
+    /// Reads `reader` to the end and decodes it with the default limits. The whole input is
+    /// buffered in memory before decoding, up to [`DecodeLimits::max_input_size`]. Fails if there
+    /// are bytes left after decoding.
+    #[cfg(feature = "std")]
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self, ReadError> {
+        Self::decode_from_with(reader, &mut DecodeContext::default())
+    }
+
+    /// Reads `reader` to the end and decodes it with the given limits and enabled proposals. The
+    /// whole input is buffered in memory before decoding. Reading stops after
+    /// [`DecodeLimits::max_input_size`] bytes, in which case this fails with
+    /// [`DecodeError::LimitExceeded`].
+    #[cfg(feature = "std")]
+    fn decode_from_with<R: Read>(
+        reader: &mut R,
+        ctx: &mut DecodeContext,
+    ) -> Result<Self, ReadError> {
+        let max_input_size = ctx.limits.max_input_size;
+
+        // Read one more byte than the limit to detect larger inputs
+        let mut bytes = Vec::new();
+        reader
+            .by_ref()
+            .take((max_input_size as u64).saturating_add(1))
+            .read_to_end(&mut bytes)?;
+        if bytes.len() > max_input_size {
+            return Err(ReadError::Decode(DecodeError::LimitExceeded(
+                "max_input_size",
+            )));
+        }
+
+        let (value, rest) = Self::decode_with(&bytes, ctx)?;
+        if !rest.is_empty() {
+            return Err(ReadError::Decode(DecodeError::Error));
+        }
+        Ok(value)
+    }
 }
 
 pub type DecodeResult<'a, T> = Result<(T, &'a [u8]), DecodeError>;
 
+/// State threaded through decoders.
+#[derive(Debug, Default, Clone)]
+pub struct DecodeContext {
+    pub limits: DecodeLimits,
+
+    pub features: WasmFeatures,
+
+    /// Current nesting depth of recursive grammar constructs, e.g. blocks.
+    depth: usize,
+}
+
+impl DecodeContext {
+    pub fn new(limits: DecodeLimits) -> Self {
+        DecodeContext {
+            limits,
+            features: WasmFeatures::default(),
+            depth: 0,
+        }
+    }
+
+    /// Called by generated decoders when entering a recursive non-terminal.
+    pub fn enter(&mut self) -> Result<(), DecodeError> {
+        if self.depth >= self.limits.max_nesting_depth {
+            return Err(DecodeError::LimitExceeded("max_nesting_depth"));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Called by generated decoders when leaving a recursive non-terminal.
+    pub fn exit(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+/// Implementation limits checked while decoding. Defaults follow the limits of the JS API, see
+/// https://webassembly.github.io/spec/js-api/#limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// Max. number of functions defined in the function and code sections.
+    pub max_functions: usize,
+
+    /// Max. number of locals in a function, excluding the parameters.
+    pub max_locals: usize,
+
+    /// Max. nesting of recursive grammar constructs, e.g. blocks in a function body. Decoding
+    /// these constructs recurses, so this also bounds the stack usage of the decoders.
+    pub max_nesting_depth: usize,
+
+    /// Max. size of a section, or any other size-prefixed part of the binary, in bytes.
+    pub max_section_size: usize,
+
+    /// Max. number of bytes read by [`Decode::decode_from_with`].
+    pub max_input_size: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        DecodeLimits {
+            max_functions: 1_000_000,
+            max_locals: 50_000,
+            max_nesting_depth: 500,
+            max_section_size: 1 << 30,
+            max_input_size: 1 << 30,
+        }
+    }
+}
+
+/// Proposals accepted while decoding. Instructions and types of a disabled proposal fail to decode
+/// with [`DecodeError::FeatureNotEnabled`].
+///
+/// Proposals disabled with cargo features can't be enabled here: their instructions and types are
+/// not in the generated types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WasmFeatures {
+    pub bulk_memory: bool,
+    pub fp16: bool,
+    pub function_references: bool,
+    pub gc: bool,
+    pub reference_types: bool,
+    pub relaxed_simd: bool,
+    pub saturating_float_to_int: bool,
+    pub sign_extension: bool,
+    pub simd: bool,
+}
+
+impl WasmFeatures {
+    /// Only the MVP, without any proposals.
+    pub fn mvp() -> Self {
+        WasmFeatures {
+            bulk_memory: false,
+            fp16: false,
+            function_references: false,
+            gc: false,
+            reference_types: false,
+            relaxed_simd: false,
+            saturating_float_to_int: false,
+            sign_extension: false,
+            simd: false,
+        }
+    }
+
+    /// Whether the proposal with the given cargo feature name is enabled, together with the
+    /// proposals it depends on. E.g. `fp16` instructions are only accepted when `simd` is enabled
+    /// too.
+    pub fn is_enabled(&self, feature: &str) -> bool {
+        match feature {
+            "bulk-memory" => self.bulk_memory,
+            "fp16" => self.fp16 && self.is_enabled("simd"),
+            "function-references" => self.function_references && self.is_enabled("reference-types"),
+            "gc" => self.gc && self.is_enabled("function-references"),
+            "reference-types" => self.reference_types,
+            "relaxed-simd" => self.relaxed_simd && self.is_enabled("simd"),
+            "saturating-float-to-int" => self.saturating_float_to_int,
+            "sign-extension" => self.sign_extension,
+            "simd" => self.simd,
+            _ => false,
+        }
+    }
+}
+
+/// All proposals are enabled by default.
+impl Default for WasmFeatures {
+    fn default() -> Self {
+        WasmFeatures {
+            bulk_memory: true,
+            fp16: true,
+            function_references: true,
+            gc: true,
+            reference_types: true,
+            relaxed_simd: true,
+            saturating_float_to_int: true,
+            sign_extension: true,
+            simd: true,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum DecodeError {
     Error,
+
+    /// A non-custom section appears after a section that it should precede. Reported by
+    /// [`Module::decode_strict`].
+    SectionOutOfOrder {
+        section: &'static str,
+        offset: usize,
+    },
+
+    /// A non-custom section appears more than once. Reported by [`Module::decode_strict`].
+    DuplicateSection {
+        section: &'static str,
+        offset: usize,
+    },
+
+    /// A limit in [`DecodeLimits`] is exceeded. The field is the name of the limit.
+    LimitExceeded(&'static str),
+
+    /// An instruction or type of a proposal disabled in [`WasmFeatures`], or of a proposal that
+    /// depends on a disabled one. The field is the name of the proposal's cargo feature, e.g.
+    /// `relaxed-simd`.
+    FeatureNotEnabled(&'static str),
+}
+
+/// Error type of [`Decode::decode_from`] and [`Decode::decode_from_with`].
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Decode(DecodeError),
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        ReadError::Io(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<DecodeError> for ReadError {
+    fn from(err: DecodeError) -> Self {
+        ReadError::Decode(err)
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -25,18 +304,206 @@ pub struct Sized<T>(pub T);
 pub struct Name(pub String);
 
 /// Zero or more `T`s. Unlive `Vec`, encoding of this type does not have a length prefix.
+///
+/// Decoding stops at the end of the buffer, or at a terminator when followed by another symbol in
+/// the grammar. See [`Repeated::decode_until`].
 #[derive(Debug, PartialEq)]
 pub struct Repeated<T>(pub Vec<T>);
 
+/// A `vec(T)` in an item of a generated reader. Elements are checked when the reader is created,
+/// and decoded again when iterated.
+pub struct VecReader<'a, T> {
+    len: u32,
+    buffer: &'a [u8],
+    phantom: PhantomData<fn() -> T>,
+}
+
+/// Contents of the function section. The number of functions is checked against
+/// [`DecodeLimits::max_functions`] before the type indices are decoded.
+#[derive(Debug, PartialEq)]
+pub struct FunctionSection(pub Vec<TypeIdx>);
+
+/// Contents of the code section. With the `parallel` feature, function bodies are decoded and
+/// encoded in parallel.
+#[derive(Debug, PartialEq)]
+pub struct CodeSection(pub Vec<Code>);
+
+/// A core module or a component. These are distinguished by the version and layer fields in the
+/// header.
+#[derive(Debug, PartialEq)]
+pub enum Binary {
+    Module(Module),
+    Component(component::Component),
+}
+
+/// Type of a `block`, `loop` or `if`. Encoded as `0x40` for no results, a value type for a single
+/// result, or a type index as a non-negative s33. Value types are negative one-byte s33s, so the
+/// encodings don't overlap.
+#[derive(Debug, PartialEq)]
+pub enum BlockType {
+    Empty,
+    Val(ValType),
+    Type(u32),
+}
+
+impl Module {
+    /// Decodes a module from `bytes`, checking that non-custom sections appear at most once and in
+    /// the order defined by the spec. Custom sections can appear anywhere.
+    ///
+    /// Unlike [`Module::decode`], this fails when there are trailing bytes after the last section.
+    pub fn decode_strict(bytes: &[u8]) -> Result<Module, DecodeError> {
+        Self::decode_strict_with(bytes, &mut DecodeContext::default())
+    }
+
+    /// Same as [`Module::decode_strict`], with the given limits.
+    pub fn decode_strict_with(
+        bytes: &[u8],
+        ctx: &mut DecodeContext,
+    ) -> Result<Module, DecodeError> {
+        let mut buffer = bytes
+            .strip_prefix(&[0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00])
+            .ok_or(DecodeError::Error)?;
+
+        let mut sections = Vec::new();
+
+        // Bit `n` is set when the section with order `n` is seen
+        let mut seen: u16 = 0;
+        let mut last_order: u8 = 0;
+
+        while !buffer.is_empty() {
+            let offset = bytes.len() - buffer.len();
+
+            let (section, buffer_) = Section::decode_with(buffer, ctx)?;
+            buffer = buffer_;
+
+            if let Some(order) = section.order() {
+                if seen & (1 << order) != 0 {
+                    return Err(DecodeError::DuplicateSection {
+                        section: section.name(),
+                        offset,
+                    });
+                }
+
+                if order < last_order {
+                    return Err(DecodeError::SectionOutOfOrder {
+                        section: section.name(),
+                        offset,
+                    });
+                }
+
+                seen |= 1 << order;
+                last_order = order;
+            }
+
+            sections.push(section);
+        }
+
+        Ok(Module(Repeated(sections)))
+    }
+}
+
+impl Section {
+    /// Name of the section, as used in the spec.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Section::Custom(_) => "custom",
+            Section::Type(_) => "type",
+            Section::Import(_) => "import",
+            Section::Function(_) => "function",
+            Section::Table(_) => "table",
+            Section::Mem(_) => "memory",
+            Section::Global(_) => "global",
+            Section::Export(_) => "export",
+            Section::Start(_) => "start",
+            Section::Element(_) => "element",
+            Section::Code(_) => "code",
+            Section::Data(_) => "data",
+            #[cfg(feature = "bulk-memory")]
+            Section::DataCount(_) => "data count",
+        }
+    }
+
+    /// Position of the section in a module. `None` for custom sections, which can appear anywhere.
+    ///
+    /// This is not the same as the section id: data count section comes before the code section.
+    fn order(&self) -> Option<u8> {
+        match self {
+            Section::Custom(_) => None,
+            Section::Type(_) => Some(1),
+            Section::Import(_) => Some(2),
+            Section::Function(_) => Some(3),
+            Section::Table(_) => Some(4),
+            Section::Mem(_) => Some(5),
+            Section::Global(_) => Some(6),
+            Section::Export(_) => Some(7),
+            Section::Start(_) => Some(8),
+            Section::Element(_) => Some(9),
+            #[cfg(feature = "bulk-memory")]
+            Section::DataCount(_) => Some(10),
+            Section::Code(_) => Some(11),
+            Section::Data(_) => Some(12),
+        }
+    }
+}
+
+/// Checks [`DecodeLimits::max_functions`] against the length of the vector at the start of a
+/// function or code section, before its elements are decoded.
+fn check_function_count(buffer: &[u8], ctx: &DecodeContext) -> Result<(), DecodeError> {
+    let (n_functions, _) = u32::decode(buffer)?;
+
+    if n_functions as usize > ctx.limits.max_functions {
+        return Err(DecodeError::LimitExceeded("max_functions"));
+    }
+
+    Ok(())
+}
+
+/// Checks [`DecodeLimits::max_locals`].
+fn check_local_count(func: &Func, ctx: &DecodeContext) -> Result<(), DecodeError> {
+    let n_locals: u64 = func.0.iter().map(|locals| u64::from(locals.0)).sum();
+
+    if n_locals > ctx.limits.max_locals as u64 {
+        return Err(DecodeError::LimitExceeded("max_locals"));
+    }
+
+    Ok(())
+}
+
+impl Custom {
+    /// Creates a custom section with the given name, with `contents` encoded as the section
+    /// contents.
+    pub fn from_contents<T: Encode>(name: &str, contents: &T) -> Custom {
+        let mut bytes = Vec::new();
+        contents.encode(&mut bytes);
+        Custom(Name(name.to_owned()), Repeated(bytes))
+    }
+
+    /// Decodes the section contents as a `T`. Fails if `T` does not consume all of the contents.
+    pub fn decode_contents<T: Decode>(&self) -> Result<T, DecodeError> {
+        let (contents, rest) = T::decode(&(self.1).0)?;
+        if !rest.is_empty() {
+            return Err(DecodeError::Error);
+        }
+        Ok(contents)
+    }
+}
+
 impl Encode for u8 {
-    fn encode(&self, buffer: &mut Vec<u8>) {
-        buffer.push(*self);
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&[*self])
+    }
+
+    fn encoded_len(&self) -> usize {
+        1
     }
 }
 
 /// Unsigned LEB128
 impl Encode for u32 {
-    fn encode(&self, buffer: &mut Vec<u8>) {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut bytes = [0u8; 5];
+        let mut len = 0;
+
         let mut val = *self;
 
         loop {
@@ -48,18 +515,28 @@ impl Encode for u32 {
                 byte |= 0b1000_0000;
             }
 
-            buffer.push(byte);
+            bytes[len] = byte;
+            len += 1;
 
             if val == 0 {
                 break;
             }
         }
+
+        writer.write_all(&bytes[..len])
+    }
+
+    fn encoded_len(&self) -> usize {
+        leb128_len(32 - self.leading_zeros())
     }
 }
 
 /// Signed LEB128
 impl Encode for i32 {
-    fn encode(&self, buffer: &mut Vec<u8>) {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut bytes = [0u8; 5];
+        let mut len = 0;
+
         let mut value = *self;
         let mut more = true;
 
@@ -73,14 +550,26 @@ impl Encode for i32 {
                 byte |= 0b1000_0000;
             }
 
-            buffer.push(byte);
+            bytes[len] = byte;
+            len += 1;
         }
+
+        writer.write_all(&bytes[..len])
+    }
+
+    fn encoded_len(&self) -> usize {
+        // Significant bits of the magnitude, plus the sign bit
+        let bits = 32 - if *self < 0 { !*self } else { *self }.leading_zeros();
+        leb128_len(bits + 1)
     }
 }
 
 /// Unsigned LEB128
 impl Encode for u64 {
-    fn encode(&self, buffer: &mut Vec<u8>) {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut bytes = [0u8; 10];
+        let mut len = 0;
+
         let mut val = *self;
 
         loop {
@@ -92,18 +581,28 @@ impl Encode for u64 {
                 byte |= 0b1000_0000;
             }
 
-            buffer.push(byte);
+            bytes[len] = byte;
+            len += 1;
 
             if val == 0 {
                 break;
             }
         }
+
+        writer.write_all(&bytes[..len])
+    }
+
+    fn encoded_len(&self) -> usize {
+        leb128_len(64 - self.leading_zeros())
     }
 }
 
 /// Signed LEB128
 impl Encode for i64 {
-    fn encode(&self, buffer: &mut Vec<u8>) {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut bytes = [0u8; 10];
+        let mut len = 0;
+
         let mut value = *self;
         let mut more = true;
 
@@ -117,60 +616,191 @@ impl Encode for i64 {
                 byte |= 0b1000_0000;
             }
 
-            buffer.push(byte);
+            bytes[len] = byte;
+            len += 1;
         }
+
+        writer.write_all(&bytes[..len])
+    }
+
+    fn encoded_len(&self) -> usize {
+        // Significant bits of the magnitude, plus the sign bit
+        let bits = 64 - if *self < 0 { !*self } else { *self }.leading_zeros();
+        leb128_len(bits + 1)
     }
 }
 
 impl Encode for f32 {
-    fn encode(&self, buffer: &mut Vec<u8>) {
-        buffer.extend_from_slice(&self.to_le_bytes());
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.to_le_bytes())
+    }
+
+    fn encoded_len(&self) -> usize {
+        4
     }
 }
 
 impl Encode for f64 {
-    fn encode(&self, buffer: &mut Vec<u8>) {
-        buffer.extend_from_slice(&self.to_le_bytes());
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.to_le_bytes())
+    }
+
+    fn encoded_len(&self) -> usize {
+        8
     }
 }
 
 impl<T: Encode> Encode for Vec<T> {
-    fn encode(&self, buffer: &mut Vec<u8>) {
-        u32::try_from(self.len()).unwrap().encode(buffer);
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        u32::try_from(self.len()).unwrap().encode_to(writer)?;
         for a in self {
-            a.encode(buffer);
+            a.encode_to(writer)?;
         }
+        Ok(())
+    }
+
+    fn encoded_len(&self) -> usize {
+        u32::try_from(self.len()).unwrap().encoded_len()
+            + self.iter().map(Encode::encoded_len).sum::<usize>()
     }
 }
 
 impl<T: Encode> Encode for Repeated<T> {
-    fn encode(&self, buffer: &mut Vec<u8>) {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
         for a in &self.0 {
-            a.encode(buffer);
+            a.encode_to(writer)?;
         }
+        Ok(())
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.0.iter().map(Encode::encoded_len).sum()
     }
 }
 
 impl<T: Encode> Encode for Sized<T> {
-    fn encode(&self, buffer: &mut Vec<u8>) {
-        let mut sized_buffer: Vec<u8> = Vec::new();
-        self.0.encode(&mut sized_buffer);
-        let size = u32::try_from(sized_buffer.len()).unwrap();
-        size.encode(buffer);
-        buffer.extend_from_slice(&sized_buffer);
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let size = u32::try_from(self.0.encoded_len()).unwrap();
+        size.encode_to(writer)?;
+        self.0.encode_to(writer)
+    }
+
+    fn encoded_len(&self) -> usize {
+        let size = self.0.encoded_len();
+        u32::try_from(size).unwrap().encoded_len() + size
+    }
+}
+
+/// Encoded with a flag byte: 0x00 for `None`, 0x01 followed by the value for `Some`.
+impl<T: Encode> Encode for Option<T> {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            None => writer.write_all(&[0x00]),
+            Some(a) => {
+                writer.write_all(&[0x01])?;
+                a.encode_to(writer)
+            }
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        1 + self.as_ref().map(Encode::encoded_len).unwrap_or(0)
     }
 }
 
+/// Tuples are encoded as their elements one after another.
+macro_rules! tuple_codec {
+    ($($t:ident $idx:tt),*) => {
+        impl<$($t: Encode),*> Encode for ($($t,)*) {
+            fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                $(self.$idx.encode_to(writer)?;)*
+                Ok(())
+            }
+
+            fn encoded_len(&self) -> usize {
+                0 $(+ self.$idx.encoded_len())*
+            }
+        }
+
+        impl<$($t: Decode),*> Decode for ($($t,)*) {
+            #[allow(non_snake_case)]
+            fn decode_with<'a>(
+                mut buffer: &'a [u8],
+                ctx: &mut DecodeContext,
+            ) -> DecodeResult<'a, Self> {
+                $(
+                    let ($t, buffer_) = $t::decode_with(buffer, ctx)?;
+                    buffer = buffer_;
+                )*
+                Ok((($($t,)*), buffer))
+            }
+        }
+    };
+}
+
+tuple_codec!(A 0, B 1);
+tuple_codec!(A 0, B 1, C 2);
+tuple_codec!(A 0, B 1, C 2, D 3);
+
 impl Encode for Name {
-    fn encode(&self, buffer: &mut Vec<u8>) {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
         let length = u32::try_from(self.0.len()).unwrap();
-        length.encode(buffer);
-        buffer.extend_from_slice(self.0.as_bytes());
+        length.encode_to(writer)?;
+        writer.write_all(self.0.as_bytes())
+    }
+
+    fn encoded_len(&self) -> usize {
+        u32::try_from(self.0.len()).unwrap().encoded_len() + self.0.len()
+    }
+}
+
+/// Fixed number of bytes, without a length prefix
+impl<const N: usize> Encode for [u8; N] {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self)
+    }
+
+    fn encoded_len(&self) -> usize {
+        N
+    }
+}
+
+impl Encode for Binary {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            Binary::Module(module) => module.encode_to(writer),
+            Binary::Component(component) => component.encode_to(writer),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self {
+            Binary::Module(module) => module.encoded_len(),
+            Binary::Component(component) => component.encoded_len(),
+        }
+    }
+}
+
+impl Encode for BlockType {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            BlockType::Empty => writer.write_all(&[0x40]),
+            BlockType::Val(t) => t.encode_to(writer),
+            BlockType::Type(x) => i64::from(*x).encode_to(writer),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self {
+            BlockType::Empty => 1,
+            BlockType::Val(t) => t.encoded_len(),
+            BlockType::Type(x) => i64::from(*x).encoded_len(),
+        }
     }
 }
 
 impl Decode for u8 {
-    fn decode(buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode_with<'a>(buffer: &'a [u8], _ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
         match buffer.first() {
             Some(byte) => Ok((*byte, &buffer[1..])),
             None => Err(DecodeError::Error),
@@ -180,7 +810,7 @@ impl Decode for u8 {
 
 /// Unsigned LEB128
 impl Decode for u32 {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode_with<'a>(mut buffer: &'a [u8], _ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
         let mut result: u32 = 0;
         let mut shift: u32 = 0;
 
@@ -192,7 +822,8 @@ impl Decode for u32 {
 
             buffer = &buffer[1..];
 
-            if shift == 31 && byte != 0x00 && byte != 0x01 {
+            // At most 5 bytes, the last one with the 4 high bits of the value
+            if shift >= 28 && byte > 0x0F {
                 return Err(DecodeError::Error);
             }
 
@@ -210,7 +841,7 @@ impl Decode for u32 {
 
 /// Signed LEB128
 impl Decode for i32 {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode_with<'a>(mut buffer: &'a [u8], _ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
         let mut result: u32 = 0;
         let mut shift: u32 = 0;
 
@@ -224,6 +855,12 @@ impl Decode for i32 {
 
             buffer = &buffer[1..];
 
+            // At most 5 bytes. Bits of the last one after the 4 high bits of the value should be
+            // the sign extension.
+            if shift >= 28 && !matches!(byte, 0x00..=0x07 | 0x78..=0x7F) {
+                return Err(DecodeError::Error);
+            }
+
             result |= ((byte & 0b0111_1111) as u32) << shift;
 
             shift += 7;
@@ -243,7 +880,7 @@ impl Decode for i32 {
 
 /// Unsigned LEB128
 impl Decode for u64 {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode_with<'a>(mut buffer: &'a [u8], _ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
         let mut result: u64 = 0;
         let mut shift: u32 = 0;
 
@@ -255,7 +892,8 @@ impl Decode for u64 {
 
             buffer = &buffer[1..];
 
-            if shift == 63 && byte != 0x00 && byte != 0x01 {
+            // At most 10 bytes, the last one with the high bit of the value
+            if shift >= 63 && byte > 0x01 {
                 return Err(DecodeError::Error);
             }
 
@@ -271,9 +909,9 @@ impl Decode for u64 {
     }
 }
 
-/// Unsigned LEB128
+/// Signed LEB128
 impl Decode for i64 {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode_with<'a>(mut buffer: &'a [u8], _ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
         let mut result: u64 = 0;
         let mut shift: u32 = 0;
 
@@ -287,6 +925,12 @@ impl Decode for i64 {
 
             buffer = &buffer[1..];
 
+            // At most 10 bytes. Bits of the last one after the high bit of the value should be the
+            // sign extension.
+            if shift >= 63 && !matches!(byte, 0x00 | 0x7F) {
+                return Err(DecodeError::Error);
+            }
+
             result |= ((byte & 0b0111_1111) as u64) << shift;
 
             shift += 7;
@@ -305,619 +949,363 @@ impl Decode for i64 {
 }
 
 impl Decode for f32 {
-    fn decode(buffer: &[u8]) -> DecodeResult<Self> {
-        // TODO: Check bounds
-        let b1 = buffer[0];
-        let b2 = buffer[1];
-        let b3 = buffer[2];
-        let b4 = buffer[3];
-        Ok((f32::from_le_bytes([b1, b2, b3, b4]), &buffer[4..]))
+    fn decode_with<'a>(buffer: &'a [u8], ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
+        let (bytes, buffer) = <[u8; 4]>::decode_with(buffer, ctx)?;
+        Ok((f32::from_le_bytes(bytes), buffer))
     }
 }
 
 impl Decode for f64 {
-    fn decode(buffer: &[u8]) -> DecodeResult<Self> {
-        // TODO: Check bounds
-        let b1 = buffer[0];
-        let b2 = buffer[1];
-        let b3 = buffer[2];
-        let b4 = buffer[3];
-        let b5 = buffer[4];
-        let b6 = buffer[5];
-        let b7 = buffer[6];
-        let b8 = buffer[7];
-        Ok((
-            f64::from_le_bytes([b1, b2, b3, b4, b5, b6, b7, b8]),
-            &buffer[8..],
-        ))
+    fn decode_with<'a>(buffer: &'a [u8], ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
+        let (bytes, buffer) = <[u8; 8]>::decode_with(buffer, ctx)?;
+        Ok((f64::from_le_bytes(bytes), buffer))
     }
 }
 
-impl<T: Decode> Decode for Vec<T> {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
-        let (length, buffer_) = u32::decode(buffer)?;
-        buffer = buffer_;
-
-        let mut vec: Vec<T> = Vec::with_capacity(length as usize);
-
-        for _ in 0..length {
-            let (a, buffer_) = T::decode(buffer)?;
-            buffer = buffer_;
-
-            vec.push(a);
+/// Fixed number of bytes, without a length prefix
+impl<const N: usize> Decode for [u8; N] {
+    fn decode_with<'a>(buffer: &'a [u8], _ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
+        match buffer.get(..N) {
+            Some(bytes) => Ok((bytes.try_into().unwrap(), &buffer[N..])),
+            None => Err(DecodeError::Error),
         }
-
-        Ok((vec, buffer))
     }
 }
 
-impl<T: Decode> Decode for Repeated<T> {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
-        let mut stuff = Vec::new();
-
-        while let Ok((thing, buffer_)) = T::decode(buffer) {
-            buffer = buffer_;
-            stuff.push(thing);
+impl Decode for Binary {
+    fn decode_with<'a>(buffer: &'a [u8], ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
+        match buffer {
+            [0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, ..] => {
+                let (module, buffer) = Module::decode_with(buffer, ctx)?;
+                Ok((Binary::Module(module), buffer))
+            }
+            [0x00, 0x61, 0x73, 0x6D, 0x0D, 0x00, 0x01, 0x00, ..] => {
+                let (component, buffer) = component::Component::decode_with(buffer, ctx)?;
+                Ok((Binary::Component(component), buffer))
+            }
+            _ => Err(DecodeError::Error),
         }
-
-        Ok((Repeated(stuff), buffer))
     }
 }
 
-impl<T: Decode> Decode for Sized<T> {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
-        let (size, buffer_) = u32::decode(buffer)?;
-        let size = size as usize;
-        buffer = buffer_;
-
-        let sized_buffer = &buffer[..size];
-        let (t, rest) = T::decode(sized_buffer)?;
-
-        if !rest.is_empty() {
-            return Err(DecodeError::Error);
+impl Decode for BlockType {
+    fn decode_with<'a>(buffer: &'a [u8], ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
+        match buffer {
+            [0x40, rest @ ..] => Ok((BlockType::Empty, rest)),
+            [0x41..=0x7F, ..] => {
+                let (t, rest) = ValType::decode_with(buffer, ctx)?;
+                Ok((BlockType::Val(t), rest))
+            }
+            _ => {
+                let (x, rest) = i64::decode_with(buffer, ctx)?;
+                // A non-negative s33, in at most 5 bytes
+                if buffer.len() - rest.len() > 5 {
+                    return Err(DecodeError::Error);
+                }
+                let x = u32::try_from(x).map_err(|_| DecodeError::Error)?;
+                Ok((BlockType::Type(x), rest))
+            }
         }
-
-        Ok((Sized(t), &buffer[size..]))
     }
 }
 
-impl Decode for Name {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
+impl<T: Decode> Decode for Vec<T> {
+    fn decode_with<'a>(mut buffer: &'a [u8], ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
         let (length, buffer_) = u32::decode(buffer)?;
-        let length = length as usize;
         buffer = buffer_;
 
-        let bytes = &buffer[..length];
-        let string = String::from_utf8(bytes.to_vec()).unwrap();
-
-        Ok((Name(string), &buffer[length..]))
-    }
-}
-
-wasm_grammar! {
-    Module {
-        0x00 0x61 0x73 0x6D // magic
-        0x01 0x00 0x00 0x00 // version
-        sections:repeated(Section) = Module,
-    }
-
-    Section {
-        // Custom section
-        0x00 custom:sized(Custom) = Custom,
-
-        // Type section
-        0x01 func_tys:sized(vec(FuncType)) = Type,
-
-        // Import section
-        0x02 imports:sized(vec(Import)) = Import,
-
-        // Function section
-        0x03 xs:sized(vec(TypeIdx)) = Function,
-
-        // Table section
-        0x04 tabs:sized(vec(Table)) = Table,
+        // Each element takes at least one byte, so don't trust lengths larger than the rest of the
+        // buffer when allocating
+        let mut vec: Vec<T> = Vec::with_capacity(core::cmp::min(length as usize, buffer.len()));
 
-        // Memory section
-        0x05 mems:sized(vec(Mem)) = Mem,
-
-        // Global section
-        0x06 globs:sized(vec(Global)) = Global,
-
-        // Export section
-        0x07 exs:sized(vec(Export)) = Export,
-
-        // Start section
-        0x08 st:sized(FuncIdx) = Start,
-
-        // Element section
-        0x09 segs:sized(vec(Elem)) = Element,
-
-        // Code section
-        0x0A codes:sized(vec(Code)) = Code,
-
-        // Data section
-        0x0B segs:sized(vec(Data)) = Data,
-
-        // Data count section
-        0x0C n:sized(u32) = DataCount,
-    }
-
-    //
-    // Custom section
-    //
-
-    Custom {
-        name:name bytes:repeated(u8) = Custom,
-    }
+        for _ in 0..length {
+            let (a, buffer_) = T::decode_with(buffer, ctx)?;
+            buffer = buffer_;
 
-    //
-    // Type section
-    //
+            vec.push(a);
+        }
 
-    FuncType {
-        0x60 r1:ResultType r2:ResultType = FuncType,
+        Ok((vec, buffer))
     }
+}
 
-    ResultType {
-        tys:vec(ValType) = ResultType,
-    }
+impl<'a, T: Decode> VecReader<'a, T> {
+    pub fn read(buffer: &'a [u8]) -> DecodeResult<'a, Self> {
+        let (len, elems) = u32::decode(buffer)?;
 
-    ValType {
-        // t:NumType = Num,
-        // t:VecType = Vec,
-        // t:RefType = Ref,
+        let mut rest = elems;
+        for _ in 0..len {
+            let (_, rest_) = T::decode(rest)?;
+            rest = rest_;
+        }
 
-        0x7F = I32,
-        0x7E = I64,
-        0x7D = F32,
-        0x7C = F64,
-        0x7B = V128,
-        0x70 = FuncRef,
-        0x6F = ExternRef,
-    }
+        let vec_reader = VecReader {
+            len,
+            buffer: &elems[..elems.len() - rest.len()],
+            phantom: PhantomData,
+        };
 
-    NumType {
-        0x7F = I32,
-        0x7E = I64,
-        0x7D = F32,
-        0x7C = F64,
+        Ok((vec_reader, rest))
     }
 
-    RefType {
-        0x70 = FuncRef,
-        0x6F = ExternRef,
+    pub fn len(&self) -> usize {
+        self.len as usize
     }
 
-    VecType {
-        0x7B = VecType, // TODO: This should be VecType::V128, but that makes the type enum
-    }
-
-    //
-    // Import section
-    //
-
-    Import {
-        module:name import_name:name desc:ImportDesc =
-            Import,
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
+}
 
-    ImportDesc {
-        0x00 x:TypeIdx = Func,
-        0x01 tt:TableType = Table,
-        0x02 mt:MemType = Mem,
-        0x03 gt:GlobalType = Global,
-    }
+impl<T: Decode> Iterator for VecReader<'_, T> {
+    type Item = T;
 
-    TableType {
-        et:RefType lim:Limits = TableType,
-    }
+    fn next(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
 
-    MemType {
-        lim:Limits = MemType,
+        // Elements are checked in `read`, so this does not fail
+        let (elem, rest) = T::decode(self.buffer).ok()?;
+        self.len -= 1;
+        self.buffer = rest;
+        Some(elem)
     }
+}
 
-    GlobalType {
-        t:ValType m:Mut = GlobalType,
+impl<T> Clone for VecReader<'_, T> {
+    fn clone(&self) -> Self {
+        *self
     }
+}
 
-    Limits {
-        0x00 n:u32 = Min,
-        0x01 n:u32 m:u32 = MinMax,
-    }
+impl<T> Copy for VecReader<'_, T> {}
 
-    Mut {
-        0x00 = Const,
-        0x01 = Mut,
+impl<T: Decode + fmt::Debug> fmt::Debug for VecReader<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(*self).finish()
     }
+}
 
-    //
-    // Table section
-    //
-
-    Table {
-        tt:TableType = Table,
+impl<T> PartialEq for VecReader<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.buffer == other.buffer
     }
+}
 
-    //
-    // Memory section
-    //
+impl<'a> OperatorsReader<'a> {
+    /// Creates a reader for the instructions of a code section entry. `code` starts with the size
+    /// of the entry. Offsets of the operators are relative to the start of `code`.
+    pub fn from_code(code: &'a [u8]) -> Result<Self, DecodeError> {
+        let (size, rest) = u32::decode(code)?;
+        let func = rest.get(..size as usize).ok_or(DecodeError::Error)?;
+
+        // Skip locals
+        let (n_locals, mut body) = u32::decode(func)?;
+        for _ in 0..n_locals {
+            let (_, rest) = Locals::decode(body)?;
+            body = rest;
+        }
 
-    Mem {
-        mt:MemType = Mem,
+        let offset = (code.len() - rest.len()) + (func.len() - body.len());
+        Ok(OperatorsReader::new(body, offset))
     }
+}
 
-    //
-    // Global section
-    //
-
-    Global {
-        gt:GlobalType e:Expr = Global,
-    }
+impl<T: Decode> Repeated<T> {
+    /// Decode `T`s until the end of the buffer, or until the buffer starts with one of the
+    /// `terminators`. Terminators are not consumed. Errors when decoding a `T` are propagated.
+    pub fn decode_until<'a>(
+        mut buffer: &'a [u8],
+        terminators: &[&[u8]],
+        ctx: &mut DecodeContext,
+    ) -> DecodeResult<'a, Self> {
+        let mut stuff = Vec::new();
 
-    //
-    // Export section
-    //
+        while !buffer.is_empty()
+            && !terminators
+                .iter()
+                .any(|terminator| buffer.starts_with(terminator))
+        {
+            let (thing, buffer_) = T::decode_with(buffer, ctx)?;
+            buffer = buffer_;
+            stuff.push(thing);
+        }
 
-    Export {
-        nm:name d:ExportDesc = Export,
+        Ok((Repeated(stuff), buffer))
     }
+}
 
-    ExportDesc {
-        0x00 x:FuncIdx = Func,
-        0x01 x:TableIdx = Table,
-        0x02 x:MemIdx = Mem,
-        0x03 x:GlobalIdx = Global,
+impl<T: Decode> Decode for Repeated<T> {
+    /// Decodes `T`s until the end of the buffer.
+    fn decode_with<'a>(buffer: &'a [u8], ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
+        Self::decode_until(buffer, &[], ctx)
     }
+}
 
-    //
-    // Element section
-    //
-
-    Elem {
-        0x00 e:Expr y:vec(FuncIdx) = E0,
-        0x01 et:ElemKind y:vec(FuncIdx) = E1,
-        0x02 x:TableIdx e:Expr et:ElemKind y:vec(FuncIdx) = E2,
-        0x03 et:ElemKind y:vec(FuncIdx) = E3,
-        0x04 e:Expr els:vec(Expr) = E4,
-        0x05 et:RefType els:vec(Expr) = E5,
-        0x06 x:TableIdx e:Expr et:RefType els:vec(Expr) = E6,
-        0x07 et:RefType els:vec(Expr) = E7,
+impl<T: Decode> Decode for Option<T> {
+    fn decode_with<'a>(buffer: &'a [u8], ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
+        match buffer {
+            [0x00, rest @ ..] => Ok((None, rest)),
+            [0x01, rest @ ..] => {
+                let (a, rest) = T::decode_with(rest, ctx)?;
+                Ok((Some(a), rest))
+            }
+            _ => Err(DecodeError::Error),
+        }
     }
+}
 
-    ElemKind {
-        0x00 = ElemKind,
-    }
+impl<T: Decode> Decode for Sized<T> {
+    fn decode_with<'a>(mut buffer: &'a [u8], ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
+        let (size, buffer_) = u32::decode(buffer)?;
+        let size = size as usize;
+        buffer = buffer_;
 
-    //
-    // Code section
-    //
+        if size > ctx.limits.max_section_size {
+            return Err(DecodeError::LimitExceeded("max_section_size"));
+        }
 
-    Code {
-        code:sized(Func) = Code,
-    }
+        let sized_buffer = buffer.get(..size).ok_or(DecodeError::Error)?;
+        let (t, rest) = T::decode_with(sized_buffer, ctx)?;
 
-    Func {
-        locals:vec(Locals) e:Expr = Func,
-    }
+        if !rest.is_empty() {
+            return Err(DecodeError::Error);
+        }
 
-    Locals {
-        n:u32 t:ValType = Locals,
+        Ok((Sized(t), &buffer[size..]))
     }
+}
 
-    //
-    // Data section
-    //
-
-    Data {
-        0x00 e:Expr bytes:vec(u8) = D0,
-        0x01 bytes:vec(u8) = D1,
-        0x02 x:MemIdx e:Expr bytes:vec(u8) = D2,
+impl Encode for FunctionSection {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.0.encode_to(writer)
     }
 
-    //
-    // Indices
-    //
-
-    TypeIdx {
-        x:u32 = TypeIdx,
+    fn encoded_len(&self) -> usize {
+        self.0.encoded_len()
     }
+}
 
-    TableIdx {
-        x:u32 = TableIdx,
+impl Decode for FunctionSection {
+    fn decode_with<'a>(buffer: &'a [u8], ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
+        check_function_count(buffer, ctx)?;
+        let (xs, buffer) = Vec::<TypeIdx>::decode_with(buffer, ctx)?;
+        Ok((FunctionSection(xs), buffer))
     }
+}
 
-    FuncIdx {
-        x:u32 = FuncIdx,
+#[cfg(not(feature = "parallel"))]
+impl Encode for CodeSection {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.0.encode_to(writer)
     }
 
-    MemIdx {
-        x:u32 = MemIdx,
+    fn encoded_len(&self) -> usize {
+        self.0.encoded_len()
     }
+}
 
-    GlobalIdx {
-        x:u32 = GlobalIdx,
+#[cfg(feature = "parallel")]
+impl Encode for CodeSection {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let codes: Vec<Vec<u8>> = self
+            .0
+            .par_iter()
+            .map(|code| {
+                let mut code_buffer = Vec::new();
+                code.encode(&mut code_buffer);
+                code_buffer
+            })
+            .collect();
+
+        u32::try_from(codes.len()).unwrap().encode_to(writer)?;
+        for code in codes {
+            writer.write_all(&code)?;
+        }
+        Ok(())
     }
 
-    LabelIdx {
-        x:u32 = LabelIdx,
+    fn encoded_len(&self) -> usize {
+        u32::try_from(self.0.len()).unwrap().encoded_len()
+            + self.0.par_iter().map(Encode::encoded_len).sum::<usize>()
     }
+}
 
-    LocalIdx {
-        x:u32 = LocalIdx,
+#[cfg(not(feature = "parallel"))]
+impl Decode for CodeSection {
+    fn decode_with<'a>(buffer: &'a [u8], ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
+        check_function_count(buffer, ctx)?;
+        let (codes, buffer) = Vec::<Code>::decode_with(buffer, ctx)?;
+        Ok((CodeSection(codes), buffer))
     }
+}
 
-    ElemIdx {
-        x:u32 = ElemIdx,
-    }
+#[cfg(feature = "parallel")]
+impl Decode for CodeSection {
+    fn decode_with<'a>(buffer: &'a [u8], ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
+        check_function_count(buffer, ctx)?;
+        let (length, mut buffer) = u32::decode(buffer)?;
 
-    DataIdx {
-        x:u32 = DataIdx,
-    }
+        // Split the entries using their sizes. When this fails the entries before the failing one
+        // are still decoded, to report the same error as the sequential decoder.
+        let mut entries: Vec<&[u8]> =
+            Vec::with_capacity(core::cmp::min(length as usize, buffer.len()));
+        let mut split_error: Option<DecodeError> = None;
 
-    //
-    // Expressions
-    //
+        for _ in 0..length {
+            let entry = u32::decode(buffer).and_then(|(size, rest)| {
+                if size as usize > ctx.limits.max_section_size {
+                    return Err(DecodeError::LimitExceeded("max_section_size"));
+                }
+                let entry_len = buffer.len() - rest.len() + size as usize;
+                buffer.get(..entry_len).ok_or(DecodeError::Error)
+            });
+
+            match entry {
+                Ok(entry) => {
+                    buffer = &buffer[entry.len()..];
+                    entries.push(entry);
+                }
+                Err(err) => {
+                    split_error = Some(err);
+                    break;
+                }
+            }
+        }
 
-    Expr {
-        instrs:repeated(Instr) 0x0B = Expr,
+        let ctx: &DecodeContext = ctx;
+        let codes: Vec<Result<Code, DecodeError>> = entries
+            .par_iter()
+            .map(|entry| {
+                let (code, rest) = Code::decode_with(entry, &mut ctx.clone())?;
+                debug_assert!(rest.is_empty());
+                Ok(code)
+            })
+            .collect();
+
+        let codes = codes
+            .into_iter()
+            .collect::<Result<Vec<Code>, DecodeError>>()?;
+
+        match split_error {
+            Some(err) => Err(err),
+            None => Ok((CodeSection(codes), buffer)),
+        }
     }
+}
 
-    Instr {
-
-        //
-        // Control instructions
-        //
-
-        0x00 = Unreachable,
-        0x01 = Nop,
-        0x02 bt:i32 instrs:repeated(Instr) 0x0B = Block,
-        0x03 bt:i32 instrs:repeated(Instr) 0x0B = Loop,
-        0x04 bt:i32 instrs:repeated(Instr) else_:Else = If,
-        0x0C l:LabelIdx = Br,
-        0x0D l:LabelIdx = BrIf,
-        0x0E ls:vec(LabelIdx) ln:LabelIdx = BrTable,
-        0x0F = Return,
-        0x10 x:FuncIdx = Call,
-        0x11 y:TypeIdx x:TableIdx = CallIndirect,
-
-        //
-        // Reference instructions
-        //
+impl Decode for Name {
+    fn decode_with<'a>(mut buffer: &'a [u8], _ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
+        let (length, buffer_) = u32::decode(buffer)?;
+        let length = length as usize;
+        buffer = buffer_;
 
-        0xD0 t:RefType = RefNull,
-        0xD1 = RefIsNull,
-        0xD2 x:FuncIdx = RefFunc,
+        let bytes = buffer.get(..length).ok_or(DecodeError::Error)?;
+        let string = String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::Error)?;
 
-        //
-        // Parametric instructions
-        //
-
-        0x1A = Drop,
-        0x1B = Select,
-        0x1C tys:vec(ValType) = SelectTys,
-
-        //
-        // Variable instructions
-        //
-
-        0x20 x:LocalIdx = LocalGet,
-        0x21 x:LocalIdx = LocalSet,
-        0x22 x:LocalIdx = LocalTee,
-        0x23 x:GlobalIdx = GlobalGet,
-        0x24 x:GlobalIdx = GlobalSet,
-
-        //
-        // Table instructions
-        //
-
-        0x25 x:TableIdx = TableGet,
-        0x26 x:TableIdx = TableSet,
-        0xFC 12:u32 y:ElemIdx x:TableIdx = TableInit,
-        0xFC 13:u32 x:ElemIdx = ElemDrop,
-        0xFC 14:u32 x:TableIdx y:TableIdx = TableCopy,
-        0xFC 15:u32 x:TableIdx = TableGrow,
-        0xFC 16:u32 x:TableIdx = TableSize,
-        0xFC 17:u32 x:TableIdx = TableFill,
-
-        //
-        // Memory instructions
-        //
-
-        0x28 m:MemArg = I32Load,
-        0x29 m:MemArg = I64Load,
-        0x2A m:MemArg = F32Load,
-        0x2B m:MemArg = F64Load,
-        0x2C m:MemArg = I32Load8S,
-        0x2D m:MemArg = I32Load8U,
-        0x2E m:MemArg = I32Load16S,
-        0x2F m:MemArg = I32Load16U,
-        0x30 m:MemArg = I64Load8S,
-        0x31 m:MemArg = I64Load8U,
-        0x32 m:MemArg = I64Load16S,
-        0x33 m:MemArg = I64Load16U,
-        0x34 m:MemArg = I64Load32S,
-        0x35 m:MemArg = I64Load32U,
-        0x36 m:MemArg = I32Store,
-        0x37 m:MemArg = I64Store,
-        0x38 m:MemArg = F32Store,
-        0x39 m:MemArg = F64Store,
-        0x3A m:MemArg = I32Store8,
-        0x3B m:MemArg = I32Store16,
-        0x3C m:MemArg = I64Store8,
-        0x3D m:MemArg = I64Store16,
-        0x3E m:MemArg = I64Store32,
-        0x3F 0u32 = MemorySize,
-        0x40 0u32 = MemoryGrow,
-        0xFC 8:u32 x:DataIdx 0u32 = MemoryInit,
-        0xFC 9:u32 x:DataIdx = DataDrop,
-        0xFC 10:u32 0u32 0u32 = MemoryCopy,
-        0xFC 11:u32 0u32 = MemoryFill,
-
-        //
-        // Numeric instructions
-        //
-
-        0x41 n:i32 = I32Const,
-        0x42 n:i64 = I64Const,
-        0x43 z:f32 = F32Const,
-        0x44 z:f64 = F64Const,
-
-        0x45 = I32Eqz,
-        0x46 = I32Eq,
-        0x47 = I32Ne,
-        0x48 = I32LtS,
-        0x49 = I32LtU,
-        0x4A = I32GtS,
-        0x4B = I32GtU,
-        0x4C = I32LeS,
-        0x4D = I32LeU,
-        0x4E = I32GeS,
-        0x4F = I32GeU,
-
-        0x50 = I64EqZ,
-        0x51 = I64Eq,
-        0x52 = I64Ne,
-        0x53 = I64LtS,
-        0x54 = I64LtU,
-        0x55 = I64GtS,
-        0x56 = I64GtU,
-        0x57 = I64LeS,
-        0x58 = I64LeU,
-        0x59 = I64GeS,
-        0x5A = I64GeU,
-
-        0x5B = F32Eq,
-        0x5C = F32Ne,
-        0x5D = F32Lt,
-        0x5E = F32Gt,
-        0x5F = F32Le,
-        0x60 = F32Ge,
-
-        0x61 = F64Eq,
-        0x62 = F64Ne,
-        0x63 = F64Lt,
-        0x64 = F64Gt,
-        0x65 = F64Le,
-        0x66 = F64Ge,
-
-        0x67 = I32Clz,
-        0x68 = I32Ctz,
-        0x69 = I32Popcnt,
-        0x6A = I32Add,
-        0x6B = I32Sub,
-        0x6C = I32Mul,
-        0x6D = I32DivS,
-        0x6E = I32DivU,
-        0x6F = I32RemS,
-        0x70 = I32RemU,
-        0x71 = I32And,
-        0x72 = I32Or,
-        0x73 = I32Xor,
-        0x74 = I32Shl,
-        0x75 = I32ShrS,
-        0x76 = I32ShrU,
-        0x77 = I32Rotl,
-        0x78 = I32Rotr,
-
-        0x79 = I64Clz,
-        0x7A = I64Ctz,
-        0x7B = I64Popcnt,
-        0x7C = I64Add,
-        0x7D = I64Sub,
-        0x7E = I64Mul,
-        0x7F = I64DivS,
-        0x80 = I64DivU,
-        0x81 = I64RemS,
-        0x82 = I64RemU,
-        0x83 = I64And,
-        0x84 = I64Or,
-        0x85 = I64Xor,
-        0x86 = I64Shl,
-        0x87 = I64ShrS,
-        0x88 = I64ShrU,
-        0x89 = I64Rotl,
-        0x8A = I64Rotr,
-
-        0x8B = F32Abs,
-        0x8C = F32Neg,
-        0x8D = F32Ceil,
-        0x8E = F32Floor,
-        0x8F = F32Trunc,
-        0x90 = F32Nearest,
-        0x91 = F32Sqrt,
-        0x92 = F32Add,
-        0x93 = F32Sub,
-        0x94 = F32Mul,
-        0x95 = F32Div,
-        0x96 = F32Min,
-        0x97 = F32Max,
-        0x98 = F32Copysign,
-
-        0x99 = F64Abs,
-        0x9A = F64Neg,
-        0x9B = F64Ceil,
-        0x9C = F64Floor,
-        0x9D = F64Trunc,
-        0x9E = F64Nearest,
-        0x9F = F64Sqrt,
-        0xA0 = F64Add,
-        0xA1 = F64Sub,
-        0xA2 = F64Mul,
-        0xA3 = F64Div,
-        0xA4 = F64Min,
-        0xA5 = F64Max,
-        0xA6 = F64Copysign,
-
-        0xA7 = I32WrapI64,
-        0xA8 = I32TruncF32S,
-        0xA9 = I32TruncF32U,
-        0xAA = I32TruncF64S,
-        0xAB = I32TruncF64U,
-        0xAC = I64ExtendI32S,
-        0xAD = I64ExtendI32U,
-        0xAE = I64TruncF32S,
-        0xAF = I64TruncF32U,
-        0xB0 = I64TruncF64S,
-        0xB1 = I64TruncF64U,
-        0xB2 = F32ConvertI32S,
-        0xB3 = F32ConvertI32U,
-        0xB4 = F32ConvertI64S,
-        0xB5 = F32ConvertI64U,
-        0xB6 = F32DemoteF64,
-        0xB7 = F64ConvertI32S,
-        0xB8 = F64ConvertI32U,
-        0xB9 = F64ConvertI64S,
-        0xBA = F64ConvertI64U,
-        0xBB = F64PromoteF32,
-        0xBC = I32ReinterpretF32,
-        0xBD = I64ReinterpretF64,
-        0xBE = F32ReinterpretI32,
-        0xBF = F64ReinterpretI64,
-
-        0xC0 = I32Extend8S,
-        0xC1 = I32Extend16S,
-        0xC2 = I64Extend8S,
-        0xC3 = I64Extend16S,
-        0xC4 = I64Extend32S,
-
-        0xFC 0:u32 = I32TruncSatF32S,
-        0xFC 1:u32 = I32TruncSatF32U,
-        0xFC 2:u32 = I32TruncSatF64S,
-        0xFC 3:u32 = I32TruncSatF64U,
-        0xFC 4:u32 = I64TruncSatF32S,
-        0xFC 5:u32 = I64TruncSatF32U,
-        0xFC 6:u32 = I64TruncSatF64S,
-        0xFC 7:u32 = I64TruncSatF64U,
-
-        // TODO: Vector instructions
-    }
-
-    Else {
-        0x0B = NoElse,
-        0x05 instrs:repeated(Instr) 0x0B = Else,
-    }
-
-    MemArg {
-        align:u32 offset:u32 = MemArg,
+        Ok((Name(string), &buffer[length..]))
     }
 }
+
+wasm_syntax_gen::include_grammar!("wasm.grammar");
@@ -0,0 +1,378 @@
+//! Custom sections of relocatable object files: `linking` and `reloc.*`.
+//!
+//! See https://github.com/WebAssembly/tool-conventions/blob/main/Linking.md
+
+use crate::{
+    wasm_grammar, Decode, DecodeContext, DecodeError, DecodeResult, Encode, Name, Repeated, Sized,
+};
+use crate::{BlockType, Code, Else, Func, Instr};
+
+use crate::io::{self, Write};
+
+use alloc::vec::Vec;
+
+/// Name of the custom section with the symbol table and other linking metadata.
+pub const LINKING_SECTION_NAME: &str = "linking";
+
+/// Prefix of the names of custom sections with relocations. The rest of the name is the name of
+/// the relocated section, e.g. `reloc.CODE`.
+pub const RELOC_SECTION_PREFIX: &str = "reloc.";
+
+wasm_grammar! {
+    extern SymInfo;
+
+    Linking {
+        2:u32 // version
+        subsections:repeated(LinkingSubsection) = Linking,
+    }
+
+    LinkingSubsection {
+        0x05 segs:sized(vec(SegmentInfo)) = SegmentInfo,
+        0x06 funcs:sized(vec(InitFunc)) = InitFuncs,
+        0x07 comdats:sized(vec(Comdat)) = ComdatInfo,
+        0x08 syms:sized(vec(SymInfo)) = SymbolTable,
+    }
+
+    SegmentInfo {
+        name:name align:u32 flags:u32 = SegmentInfo,
+    }
+
+    InitFunc {
+        priority:u32 sym:u32 = InitFunc,
+    }
+
+    Comdat {
+        name:name flags:u32 syms:vec(ComdatSym) = Comdat,
+    }
+
+    ComdatSym {
+        0x00 x:u32 = Data,
+        0x01 x:u32 = Function,
+        0x05 x:u32 = Section,
+    }
+
+    DataSymDef {
+        x:u32 offset:u64 size:u64 = DataSymDef,
+    }
+
+    Reloc {
+        section:u32 entries:vec(RelocEntry) = Reloc,
+    }
+
+    RelocEntry {
+        0x00 offset:u32 index:u32 = FunctionIndexLeb,
+        0x01 offset:u32 index:u32 = TableIndexSleb,
+        0x02 offset:u32 index:u32 = TableIndexI32,
+        0x03 offset:u32 index:u32 addend:i32 = MemoryAddrLeb,
+        0x04 offset:u32 index:u32 addend:i32 = MemoryAddrSleb,
+        0x05 offset:u32 index:u32 addend:i32 = MemoryAddrI32,
+        0x06 offset:u32 index:u32 = TypeIndexLeb,
+        0x07 offset:u32 index:u32 = GlobalIndexLeb,
+        0x08 offset:u32 index:u32 addend:i32 = FunctionOffsetI32,
+        0x09 offset:u32 index:u32 addend:i32 = SectionOffsetI32,
+        0x0A offset:u32 index:u32 = TagIndexLeb,
+        0x0B offset:u32 index:u32 addend:i32 = MemoryAddrRelSleb,
+        0x0C offset:u32 index:u32 = TableIndexRelSleb,
+        0x0D offset:u32 index:u32 = GlobalIndexI32,
+        0x0E offset:u32 index:u32 addend:i64 = MemoryAddrLeb64,
+        0x0F offset:u32 index:u32 addend:i64 = MemoryAddrSleb64,
+        0x10 offset:u32 index:u32 addend:i64 = MemoryAddrI64,
+        0x11 offset:u32 index:u32 addend:i64 = MemoryAddrRelSleb64,
+        0x12 offset:u32 index:u32 = TableIndexSleb64,
+        0x13 offset:u32 index:u32 = TableIndexI64,
+        0x14 offset:u32 index:u32 = TableNumberLeb,
+        0x15 offset:u32 index:u32 addend:i32 = MemoryAddrTlsSleb,
+        0x16 offset:u32 index:u32 addend:i64 = FunctionOffsetI64,
+        0x17 offset:u32 index:u32 addend:i32 = MemoryAddrLocrelI32,
+        0x18 offset:u32 index:u32 = TableIndexRelSleb64,
+        0x19 offset:u32 index:u32 addend:i64 = MemoryAddrTlsSleb64,
+        0x1A offset:u32 index:u32 = FunctionIndexI32,
+    }
+}
+
+const WASM_SYM_UNDEFINED: u32 = 0x10;
+const WASM_SYM_EXPLICIT_NAME: u32 = 0x40;
+
+/// A symbol table entry. The first field of each variant is the symbol flags.
+///
+/// Whether the name and definition of a symbol are present depends on the flags, so this is not
+/// defined in the grammar.
+#[derive(Debug, PartialEq)]
+pub enum SymInfo {
+    Function(u32, u32, Option<Name>),
+    Data(u32, Name, Option<DataSymDef>),
+    Global(u32, u32, Option<Name>),
+    Section(u32, u32),
+    Tag(u32, u32, Option<Name>),
+    Table(u32, u32, Option<Name>),
+}
+
+impl Encode for SymInfo {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            SymInfo::Function(flags, index, name) => encode_sym(0, *flags, *index, name, writer),
+            SymInfo::Data(flags, name, def) => {
+                writer.write_all(&[1])?;
+                flags.encode_to(writer)?;
+                name.encode_to(writer)?;
+                if let Some(def) = def {
+                    def.encode_to(writer)?;
+                }
+                Ok(())
+            }
+            SymInfo::Global(flags, index, name) => encode_sym(2, *flags, *index, name, writer),
+            SymInfo::Section(flags, index) => {
+                writer.write_all(&[3])?;
+                flags.encode_to(writer)?;
+                index.encode_to(writer)
+            }
+            SymInfo::Tag(flags, index, name) => encode_sym(4, *flags, *index, name, writer),
+            SymInfo::Table(flags, index, name) => encode_sym(5, *flags, *index, name, writer),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self {
+            SymInfo::Function(flags, index, name)
+            | SymInfo::Global(flags, index, name)
+            | SymInfo::Tag(flags, index, name)
+            | SymInfo::Table(flags, index, name) => {
+                1 + flags.encoded_len()
+                    + index.encoded_len()
+                    + name.as_ref().map_or(0, Encode::encoded_len)
+            }
+            SymInfo::Data(flags, name, def) => {
+                1 + flags.encoded_len()
+                    + name.encoded_len()
+                    + def.as_ref().map_or(0, Encode::encoded_len)
+            }
+            SymInfo::Section(flags, index) => 1 + flags.encoded_len() + index.encoded_len(),
+        }
+    }
+}
+
+fn encode_sym<W: Write>(
+    kind: u8,
+    flags: u32,
+    index: u32,
+    name: &Option<Name>,
+    writer: &mut W,
+) -> io::Result<()> {
+    writer.write_all(&[kind])?;
+    flags.encode_to(writer)?;
+    index.encode_to(writer)?;
+    if let Some(name) = name {
+        name.encode_to(writer)?;
+    }
+    Ok(())
+}
+
+impl Decode for SymInfo {
+    fn decode_with<'a>(buffer: &'a [u8], ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
+        let (kind, buffer) = u8::decode(buffer)?;
+        let (flags, buffer) = u32::decode(buffer)?;
+        let undefined = flags & WASM_SYM_UNDEFINED != 0;
+
+        match kind {
+            0 | 2 | 4 | 5 => {
+                let (index, mut buffer) = u32::decode(buffer)?;
+
+                let name = if !undefined || flags & WASM_SYM_EXPLICIT_NAME != 0 {
+                    let (name, buffer_) = Name::decode_with(buffer, ctx)?;
+                    buffer = buffer_;
+                    Some(name)
+                } else {
+                    None
+                };
+
+                let sym = match kind {
+                    0 => SymInfo::Function(flags, index, name),
+                    2 => SymInfo::Global(flags, index, name),
+                    4 => SymInfo::Tag(flags, index, name),
+                    _ => SymInfo::Table(flags, index, name),
+                };
+
+                Ok((sym, buffer))
+            }
+            1 => {
+                let (name, mut buffer) = Name::decode_with(buffer, ctx)?;
+
+                let def = if !undefined {
+                    let (def, buffer_) = DataSymDef::decode_with(buffer, ctx)?;
+                    buffer = buffer_;
+                    Some(def)
+                } else {
+                    None
+                };
+
+                Ok((SymInfo::Data(flags, name, def), buffer))
+            }
+            3 => {
+                let (index, buffer) = u32::decode(buffer)?;
+                Ok((SymInfo::Section(flags, index), buffer))
+            }
+            _ => Err(DecodeError::Error),
+        }
+    }
+}
+
+impl RelocEntry {
+    /// Offset of the relocated value, relative to the start of the contents of the relocated
+    /// section.
+    pub fn offset(&self) -> u32 {
+        match self {
+            RelocEntry::FunctionIndexLeb(offset, ..)
+            | RelocEntry::TableIndexSleb(offset, ..)
+            | RelocEntry::TableIndexI32(offset, ..)
+            | RelocEntry::MemoryAddrLeb(offset, ..)
+            | RelocEntry::MemoryAddrSleb(offset, ..)
+            | RelocEntry::MemoryAddrI32(offset, ..)
+            | RelocEntry::TypeIndexLeb(offset, ..)
+            | RelocEntry::GlobalIndexLeb(offset, ..)
+            | RelocEntry::FunctionOffsetI32(offset, ..)
+            | RelocEntry::SectionOffsetI32(offset, ..)
+            | RelocEntry::TagIndexLeb(offset, ..)
+            | RelocEntry::MemoryAddrRelSleb(offset, ..)
+            | RelocEntry::TableIndexRelSleb(offset, ..)
+            | RelocEntry::GlobalIndexI32(offset, ..)
+            | RelocEntry::MemoryAddrLeb64(offset, ..)
+            | RelocEntry::MemoryAddrSleb64(offset, ..)
+            | RelocEntry::MemoryAddrI64(offset, ..)
+            | RelocEntry::MemoryAddrRelSleb64(offset, ..)
+            | RelocEntry::TableIndexSleb64(offset, ..)
+            | RelocEntry::TableIndexI64(offset, ..)
+            | RelocEntry::TableNumberLeb(offset, ..)
+            | RelocEntry::MemoryAddrTlsSleb(offset, ..)
+            | RelocEntry::FunctionOffsetI64(offset, ..)
+            | RelocEntry::MemoryAddrLocrelI32(offset, ..)
+            | RelocEntry::TableIndexRelSleb64(offset, ..)
+            | RelocEntry::MemoryAddrTlsSleb64(offset, ..)
+            | RelocEntry::FunctionIndexI32(offset, ..) => *offset,
+        }
+    }
+}
+
+/// The instruction patched by a relocation in the code section.
+#[derive(Debug, PartialEq)]
+pub struct CodeRelocTarget<'a> {
+    /// Index of the function in the code section. Imported functions are not counted.
+    pub func: usize,
+
+    /// The patched instruction. For `block`, `loop` and `if` this is the instruction with the
+    /// patched block type.
+    pub instr: &'a Instr,
+}
+
+/// Resolves relocations of a `reloc.CODE` section to the instructions they patch.
+///
+/// `code_section` is the contents of the code section, without the section id and size, as
+/// relocation offsets are relative to it. `codes` should be decoded from `code_section`. Object
+/// files pad relocated LEB128s, so byte offsets can't be computed from `codes` alone.
+///
+/// The returned vector has one element for each relocation. Relocations that don't point into an
+/// instruction are resolved as `None`.
+pub fn resolve_code_relocs<'a>(
+    code_section: &[u8],
+    codes: &'a [Code],
+    relocs: &[RelocEntry],
+) -> Result<Vec<Option<CodeRelocTarget<'a>>>, DecodeError> {
+    // Byte ranges of instructions, sorted by start offset, as the instructions are visited in
+    // order
+    let mut spans: Vec<InstrSpan<'a>> = Vec::new();
+
+    let (n_codes, mut buffer) = u32::decode(code_section)?;
+    if n_codes as usize != codes.len() {
+        return Err(DecodeError::Error);
+    }
+
+    for (func_idx, code) in codes.iter().enumerate() {
+        let (size, buffer_) = u32::decode(buffer)?;
+        let body = buffer_.get(..size as usize).ok_or(DecodeError::Error)?;
+        buffer = &buffer_[size as usize..];
+
+        let (_, instrs) = Vec::<crate::Locals>::decode(body)?;
+        let instrs_start = code_section.len() - buffer.len() - instrs.len();
+
+        let Func(_, expr) = &(code.0).0;
+        collect_instr_spans(
+            code_section,
+            instrs_start,
+            &(expr.0).0,
+            func_idx,
+            &mut spans,
+        )?;
+    }
+
+    Ok(relocs
+        .iter()
+        .map(|reloc| {
+            let offset = reloc.offset() as usize;
+            let span_idx = spans.partition_point(|span| span.start <= offset);
+            span_idx
+                .checked_sub(1)
+                .map(|span_idx| &spans[span_idx])
+                .filter(|span| offset < span.end)
+                .map(|span| CodeRelocTarget {
+                    func: span.func,
+                    instr: span.instr,
+                })
+        })
+        .collect())
+}
+
+struct InstrSpan<'a> {
+    start: usize,
+    end: usize,
+    func: usize,
+    instr: &'a Instr,
+}
+
+/// Adds spans of `instrs`, starting at `pos` in `bytes`, to `spans`. Returns the position after
+/// the instructions.
+fn collect_instr_spans<'a>(
+    bytes: &[u8],
+    mut pos: usize,
+    instrs: &'a [Instr],
+    func: usize,
+    spans: &mut Vec<InstrSpan<'a>>,
+) -> Result<usize, DecodeError> {
+    for instr in instrs {
+        let start = pos;
+        let instr_bytes = bytes.get(start..).ok_or(DecodeError::Error)?;
+
+        match instr {
+            Instr::Block(_, body) | Instr::Loop(_, body) | Instr::If(_, body, _) => {
+                // Span of a block is the opcode and block type
+                let (_, rest) = BlockType::decode(instr_bytes.get(1..).ok_or(DecodeError::Error)?)?;
+                let end = bytes.len() - rest.len();
+                spans.push(InstrSpan {
+                    start,
+                    end,
+                    func,
+                    instr,
+                });
+
+                pos = collect_instr_spans(bytes, end, &body.0, func, spans)?;
+
+                if let Instr::If(_, _, Else::Else(else_body)) = instr {
+                    pos = collect_instr_spans(bytes, pos + 1, &else_body.0, func, spans)?;
+                }
+
+                // `end`
+                pos += 1;
+            }
+            _ => {
+                let (_, rest) = Instr::decode(instr_bytes)?;
+                pos = bytes.len() - rest.len();
+                spans.push(InstrSpan {
+                    start,
+                    end: pos,
+                    func,
+                    instr,
+                });
+            }
+        }
+    }
+
+    Ok(pos)
+}
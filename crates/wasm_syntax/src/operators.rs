@@ -0,0 +1,191 @@
+//! Flat representation of expressions, as a sequence of operators with explicit `block`, `loop`,
+//! `if`, `else` and `end` markers.
+
+use crate::{
+    BlockType, Decode, DecodeContext, DecodeError, DecodeResult, Else, Encode, Expr, Instr,
+    Repeated,
+};
+
+use crate::io::{self, Write};
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// An operator in a flat instruction sequence. Structured instructions are split into markers,
+/// other instructions are stored as they are.
+#[derive(Debug, PartialEq)]
+pub enum Operator {
+    Block(BlockType),
+    Loop(BlockType),
+    If(BlockType),
+    Else,
+    End,
+
+    /// A non-structured instruction. This is never `Instr::Block`, `Instr::Loop` or `Instr::If`.
+    Instr(Instr),
+}
+
+impl Expr {
+    /// Flattens the expression. The last operator is the `end` of the expression.
+    pub fn into_operators(self) -> Vec<Operator> {
+        let mut operators = Vec::new();
+
+        // Instructions of the enclosing blocks, with the `else` branch of `if` blocks
+        let mut stack: Vec<(alloc::vec::IntoIter<Instr>, Option<Vec<Instr>>)> =
+            vec![((self.0).0.into_iter(), None)];
+
+        while let Some((instrs, else_instrs)) = stack.last_mut() {
+            let (operator, block) = match instrs.next() {
+                Some(Instr::Block(bt, Repeated(body))) => (Operator::Block(bt), Some((body, None))),
+                Some(Instr::Loop(bt, Repeated(body))) => (Operator::Loop(bt), Some((body, None))),
+                Some(Instr::If(bt, Repeated(body), else_)) => {
+                    let else_body = match else_ {
+                        Else::NoElse() => None,
+                        Else::Else(Repeated(else_body)) => Some(else_body),
+                    };
+                    (Operator::If(bt), Some((body, else_body)))
+                }
+                Some(instr) => (Operator::Instr(instr), None),
+                None => match else_instrs.take() {
+                    Some(else_body) => {
+                        *instrs = else_body.into_iter();
+                        (Operator::Else, None)
+                    }
+                    None => {
+                        stack.pop();
+                        (Operator::End, None)
+                    }
+                },
+            };
+
+            operators.push(operator);
+
+            if let Some((body, else_body)) = block {
+                stack.push((body.into_iter(), else_body));
+            }
+        }
+
+        operators
+    }
+
+    /// Builds an expression from a flat operator sequence, as returned by
+    /// [`Expr::into_operators`]. Fails if the markers are not balanced, or there are operators
+    /// after the `end` of the expression.
+    pub fn from_operators<I: IntoIterator<Item = Operator>>(
+        operators: I,
+    ) -> Result<Expr, DecodeError> {
+        enum Enclosing {
+            Block(BlockType),
+            Loop(BlockType),
+            /// The `then` branch is `Some` after the `else` marker
+            If(BlockType, Option<Vec<Instr>>),
+        }
+
+        let mut operators = operators.into_iter();
+
+        // Enclosing blocks, with the instructions collected so far
+        let mut stack: Vec<(Enclosing, Vec<Instr>)> = Vec::new();
+        let mut instrs: Vec<Instr> = Vec::new();
+
+        loop {
+            match operators.next().ok_or(DecodeError::Error)? {
+                Operator::Block(bt) => {
+                    stack.push((Enclosing::Block(bt), core::mem::take(&mut instrs)))
+                }
+                Operator::Loop(bt) => {
+                    stack.push((Enclosing::Loop(bt), core::mem::take(&mut instrs)))
+                }
+                Operator::If(bt) => {
+                    stack.push((Enclosing::If(bt, None), core::mem::take(&mut instrs)))
+                }
+                Operator::Else => match stack.last_mut() {
+                    Some((Enclosing::If(_, then_instrs @ None), _)) => {
+                        *then_instrs = Some(core::mem::take(&mut instrs));
+                    }
+                    _ => return Err(DecodeError::Error),
+                },
+                Operator::End => {
+                    let (block, parent_instrs) = match stack.pop() {
+                        Some(block) => block,
+                        None => break,
+                    };
+
+                    let body = Repeated(core::mem::replace(&mut instrs, parent_instrs));
+                    instrs.push(match block {
+                        Enclosing::Block(bt) => Instr::Block(bt, body),
+                        Enclosing::Loop(bt) => Instr::Loop(bt, body),
+                        Enclosing::If(bt, None) => Instr::If(bt, body, Else::NoElse()),
+                        Enclosing::If(bt, Some(then_instrs)) => {
+                            Instr::If(bt, Repeated(then_instrs), Else::Else(body))
+                        }
+                    });
+                }
+                Operator::Instr(Instr::Block(..) | Instr::Loop(..) | Instr::If(..)) => {
+                    return Err(DecodeError::Error)
+                }
+                Operator::Instr(instr) => instrs.push(instr),
+            }
+        }
+
+        if operators.next().is_some() {
+            return Err(DecodeError::Error);
+        }
+
+        Ok(Expr(Repeated(instrs)))
+    }
+}
+
+impl Encode for Operator {
+    fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            Operator::Block(bt) => {
+                writer.write_all(&[0x02])?;
+                bt.encode_to(writer)
+            }
+            Operator::Loop(bt) => {
+                writer.write_all(&[0x03])?;
+                bt.encode_to(writer)
+            }
+            Operator::If(bt) => {
+                writer.write_all(&[0x04])?;
+                bt.encode_to(writer)
+            }
+            Operator::Else => writer.write_all(&[0x05]),
+            Operator::End => writer.write_all(&[0x0B]),
+            Operator::Instr(instr) => instr.encode_to(writer),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self {
+            Operator::Block(bt) | Operator::Loop(bt) | Operator::If(bt) => 1 + bt.encoded_len(),
+            Operator::Else | Operator::End => 1,
+            Operator::Instr(instr) => instr.encoded_len(),
+        }
+    }
+}
+
+impl Decode for Operator {
+    fn decode_with<'a>(buffer: &'a [u8], ctx: &mut DecodeContext) -> DecodeResult<'a, Self> {
+        match buffer {
+            [0x02, rest @ ..] => {
+                let (bt, rest) = BlockType::decode_with(rest, ctx)?;
+                Ok((Operator::Block(bt), rest))
+            }
+            [0x03, rest @ ..] => {
+                let (bt, rest) = BlockType::decode_with(rest, ctx)?;
+                Ok((Operator::Loop(bt), rest))
+            }
+            [0x04, rest @ ..] => {
+                let (bt, rest) = BlockType::decode_with(rest, ctx)?;
+                Ok((Operator::If(bt), rest))
+            }
+            [0x05, rest @ ..] => Ok((Operator::Else, rest)),
+            [0x0B, rest @ ..] => Ok((Operator::End, rest)),
+            _ => {
+                let (instr, rest) = Instr::decode_with(buffer, ctx)?;
+                Ok((Operator::Instr(instr), rest))
+            }
+        }
+    }
+}
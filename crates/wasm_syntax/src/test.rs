@@ -35,6 +35,34 @@ fn test_i32_leb128_decode() {
     );
 }
 
+#[test]
+fn test_leb128_decode_overflow() {
+    assert_eq!(
+        u32::decode(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F]).unwrap(),
+        (u32::MAX, [].as_ref())
+    );
+    assert!(u32::decode(&[0xFF, 0xFF, 0xFF, 0xFF, 0x1F]).is_err());
+    assert!(u32::decode(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x01]).is_err());
+
+    assert_eq!(
+        i32::decode(&[0x80, 0x80, 0x80, 0x80, 0x78]).unwrap(),
+        (i32::MIN, [].as_ref())
+    );
+    assert!(i32::decode(&[0x80, 0x80, 0x80, 0x80, 0x70]).is_err());
+    assert!(i32::decode(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00]).is_err());
+
+    assert!(
+        u64::decode(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01]).is_err()
+    );
+    assert!(
+        i64::decode(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00]).is_err()
+    );
+    assert_eq!(
+        i64::decode(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7F]).unwrap(),
+        (i64::MIN, [].as_ref())
+    );
+}
+
 #[test]
 fn test_repeated_decode() {
     let bytes = [0x02, 0x03, 0x01, 0x00, 0x00];
@@ -43,6 +71,43 @@ fn test_repeated_decode() {
     assert_eq!(xs.0, bytes);
 }
 
+#[test]
+fn test_repeated_decode_until() {
+    let bytes = [0x02, 0x03, 0x0B, 0x01];
+    let (xs, rest): (Repeated<u8>, _) =
+        Repeated::decode_until(&bytes, &[&[0x0B]], &mut DecodeContext::default()).unwrap();
+    assert_eq!(xs.0, [0x02, 0x03]);
+    assert_eq!(rest, [0x0B, 0x01]);
+
+    // Corrupt section after a valid one is an error, not a leftover
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x01, 0x00, // type section
+        0x0F, 0x01, 0x00, // unknown section
+    ];
+    assert_eq!(Module::decode(&bytes), Err(DecodeError::Error));
+
+    // Same for an invalid instruction in a block
+    let bytes = [0x02, 0x40, 0x41, 0x00, 0xFF, 0x0B, 0x0B];
+    assert_eq!(Expr::decode(&bytes), Err(DecodeError::Error));
+}
+
+#[test]
+fn test_option_tuple_decode() {
+    let bytes = [0x01, 0x02, 0x00, 0x03];
+    let ((x, y, z), rest): ((Option<u8>, Option<u8>, u8), _) = Decode::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+    assert_eq!((x, y, z), (Some(2), None, 3));
+
+    let mut buffer = vec![];
+    (x, y, z).encode(&mut buffer);
+    assert_eq!(buffer, bytes);
+    assert_eq!((x, y, z).encoded_len(), bytes.len());
+
+    // Flag byte can only be 0 or 1
+    assert_eq!(Option::<u8>::decode(&[0x02, 0x00]), Err(DecodeError::Error));
+}
+
 #[test]
 fn test_custom_section_decode() {
     let bytes = [
@@ -55,3 +120,1106 @@ fn test_custom_section_decode() {
     assert_eq!(name.0.as_str(), "name");
     assert_eq!(contents.0, [0x02, 0x03, 0x01, 0x00, 0x00]);
 }
+
+#[test]
+fn test_val_type_decode() {
+    let types: [(&[u8], ValType); 2] = [
+        (&[0x7E], ValType::Num(NumType::I64())),
+        (&[0x70], ValType::Ref(RefType::FuncRef())),
+    ];
+
+    for (bytes, ty) in types {
+        assert_eq!(ValType::decode(bytes), Ok((ty, &[][..])));
+
+        let (ty, _) = ValType::decode(bytes).unwrap();
+        let mut buffer = vec![];
+        ty.encode(&mut buffer);
+        assert_eq!(buffer, bytes);
+    }
+
+    #[cfg(feature = "reference-types")]
+    assert_eq!(
+        ValType::decode(&[0x6F]),
+        Ok((ValType::Ref(RefType::ExternRef()), &[][..]))
+    );
+
+    #[cfg(feature = "simd")]
+    assert_eq!(
+        ValType::decode(&[0x7B]),
+        Ok((ValType::Vec(VecType()), &[][..]))
+    );
+
+    #[cfg(feature = "function-references")]
+    assert_eq!(
+        ValType::decode(&[0x63, 0x70]),
+        Ok((ValType::Ref(RefType::RefNull(HeapType::Func())), &[][..]))
+    );
+
+    assert_eq!(ValType::decode(&[0x40]), Err(DecodeError::Error));
+}
+
+#[cfg(feature = "gc")]
+#[test]
+fn test_gc_type_section_decode() {
+    let bytes = [
+        0x01, 0x12, // type section, size = 18
+        0x02, // 2 recursive types
+        0x60, 0x00, 0x00, // (func)
+        0x4E, 0x02, // (rec
+        0x50, 0x00, 0x5F, 0x01, 0x78, 0x01, // (sub (struct (field (mut i8))))
+        0x4F, 0x01, 0x01, 0x5E, 0x6E, 0x00, // (sub final 1 (array anyref)))
+    ];
+    let (section, rest) = Section::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+    assert_eq!(
+        section,
+        Section::Type(Sized(vec![
+            RecType::Sub(SubType::Final(CompType::Func(FuncType(
+                ResultType(vec![]),
+                ResultType(vec![])
+            )))),
+            RecType::Rec(vec![
+                SubType::Sub(
+                    vec![],
+                    CompType::Struct(vec![FieldType(StorageType::I8(), Mut::Mut())])
+                ),
+                SubType::SubFinal(
+                    vec![TypeIdx(1)],
+                    CompType::Array(FieldType(
+                        StorageType::Val(ValType::Ref(RefType::AnyRef())),
+                        Mut::Const()
+                    ))
+                ),
+            ]),
+        ]))
+    );
+
+    let mut buffer = vec![];
+    section.encode(&mut buffer);
+    assert_eq!(buffer, bytes);
+}
+
+#[cfg(feature = "gc")]
+#[test]
+fn test_gc_instr_decode() {
+    // br_on_cast 0 anyref (ref null 3)
+    let bytes = [0xFB, 0x18, 0x02, 0x00, 0x6E, 0x03];
+    let (instr, rest) = Instr::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+    assert_eq!(
+        instr,
+        Instr::BrOnCast(2, LabelIdx(0), HeapType::Any(), HeapType::Type(3))
+    );
+
+    let mut buffer = vec![];
+    instr.encode(&mut buffer);
+    assert_eq!(buffer, bytes);
+}
+
+#[cfg(feature = "function-references")]
+#[test]
+fn test_typed_func_ref_table_decode() {
+    let bytes = [
+        0x40, 0x00, // table with initializer
+        0x63, 0x02, // (ref null 2)
+        0x00, 0x01, // min = 1
+        0xD0, 0x02, // ref.null 2
+        0xD4, // ref.as_non_null
+        0x0B, // end
+    ];
+    let (table, rest) = Table::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+    assert_eq!(
+        table,
+        Table::TableInit(
+            TableType(RefType::RefNull(HeapType::Type(2)), Limits::Min(1)),
+            Expr(Repeated(vec![
+                Instr::RefNull(HeapType::Type(2)),
+                Instr::RefAsNonNull()
+            ]))
+        )
+    );
+
+    let mut buffer = vec![];
+    table.encode(&mut buffer);
+    assert_eq!(buffer, bytes);
+
+    let mut ctx = DecodeContext::default();
+    ctx.features.function_references = false;
+    assert_eq!(
+        Table::decode_with(&bytes, &mut ctx),
+        Err(DecodeError::FeatureNotEnabled("function-references"))
+    );
+}
+
+#[cfg(feature = "simd")]
+#[test]
+fn test_simd_instr_decode() {
+    let mut bytes = vec![0xFD, 0x0C]; // v128.const
+    bytes.extend(0..16);
+    bytes.extend([0xFD, 0x0D]); // i8x16.shuffle
+    bytes.extend(16..32);
+    bytes.extend([0xFD, 0x15, 0x03]); // i8x16.extract_lane_s 3
+    bytes.push(0x0B);
+
+    let (expr, rest) = Expr::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+    assert_eq!(
+        expr,
+        Expr(Repeated(vec![
+            Instr::V128Const([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]),
+            Instr::I8x16Shuffle([16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31]),
+            Instr::I8x16ExtractLaneS(LaneIdx(3)),
+        ]))
+    );
+
+    let mut buffer = vec![];
+    expr.encode(&mut buffer);
+    assert_eq!(buffer, bytes);
+
+    // Shuffle lanes index two vectors of 16 lanes
+    let mut shuffle = vec![0xFD, 0x0D];
+    shuffle.extend(16..32);
+    shuffle[2 + 15] = 32;
+    assert_eq!(Instr::decode(&shuffle), Err(DecodeError::Error));
+
+    // Not enough bytes
+    assert_eq!(Instr::decode(&bytes[..10]), Err(DecodeError::Error));
+}
+
+#[cfg(feature = "function-references")]
+#[test]
+fn test_s33_decode() {
+    let check = |bytes: &[u8], ty: Option<HeapType>| match ty {
+        Some(ty) => assert_eq!(HeapType::decode(bytes), Ok((ty, &[][..]))),
+        None => assert_eq!(HeapType::decode(bytes), Err(DecodeError::Error)),
+    };
+
+    check(&[0x03], Some(HeapType::Type(3)));
+    check(&[0xC0, 0x00], Some(HeapType::Type(64)));
+    check(
+        &[0xFF, 0xFF, 0xFF, 0xFF, 0x0F],
+        Some(HeapType::Type(u32::MAX)),
+    );
+
+    // Encoded as signed
+    let mut buffer = vec![];
+    HeapType::Type(64).encode(&mut buffer);
+    assert_eq!(buffer, [0xC0, 0x00]);
+
+    // Negative
+    check(&[0x40], None);
+    check(&[0x7F], None);
+    check(&[0x80, 0x80, 0x80, 0x80, 0x70], None);
+
+    // Out of range
+    check(&[0x80, 0x80, 0x80, 0x80, 0x10], None);
+    check(&[0xFF, 0xFF, 0xFF, 0xFF, 0x6F], None);
+
+    // More than 5 bytes
+    check(&[0x83, 0x80, 0x80, 0x80, 0x80, 0x00], None);
+}
+
+#[test]
+fn test_block_type_decode() {
+    let check = |bytes: &[u8], bt: Option<BlockType>| match bt {
+        Some(bt) => {
+            assert_eq!(BlockType::decode(bytes), Ok((bt, &[][..])));
+            let (bt, _) = BlockType::decode(bytes).unwrap();
+            let mut buffer = vec![];
+            bt.encode(&mut buffer);
+            assert_eq!(buffer, bytes);
+        }
+        None => assert!(BlockType::decode(bytes).is_err()),
+    };
+
+    check(&[0x40], Some(BlockType::Empty));
+    check(&[0x7F], Some(BlockType::Val(ValType::Num(NumType::I32()))));
+    check(&[0x05], Some(BlockType::Type(5)));
+    check(&[0xC0, 0x00], Some(BlockType::Type(64)));
+    check(
+        &[0xFF, 0xFF, 0xFF, 0xFF, 0x0F],
+        Some(BlockType::Type(u32::MAX)),
+    );
+
+    // Not a value type
+    check(&[0x41], None);
+
+    // Negative type index
+    check(&[0xFF, 0x7F], None);
+
+    // More than 5 bytes
+    check(&[0x85, 0x80, 0x80, 0x80, 0x80, 0x00], None);
+}
+
+#[test]
+fn test_float_decode() {
+    assert_eq!(f32::decode(&[0x00, 0x00, 0x80, 0x3F]), Ok((1.0, &[][..])));
+    assert_eq!(f32::decode(&[0x00, 0x00]), Err(DecodeError::Error));
+    assert_eq!(f64::decode(&[0x00; 7]), Err(DecodeError::Error));
+}
+
+#[cfg(not(feature = "simd"))]
+#[test]
+fn test_simd_instr_decode_disabled() {
+    assert_eq!(Instr::decode(&[0xFD, 0x0F]), Err(DecodeError::Error)); // i8x16.splat
+    assert_eq!(ValType::decode(&[0x7B]), Err(DecodeError::Error));
+}
+
+#[cfg(feature = "relaxed-simd")]
+#[test]
+fn test_relaxed_simd_instr_decode() {
+    let bytes = [0xFD, 0x85, 0x02]; // f32x4.relaxed_madd
+    let (instr, rest) = Instr::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+    assert_eq!(instr, Instr::F32x4RelaxedMadd());
+
+    let mut buffer = vec![];
+    instr.encode(&mut buffer);
+    assert_eq!(buffer, bytes);
+}
+
+#[cfg(not(feature = "relaxed-simd"))]
+#[test]
+fn test_relaxed_simd_instr_decode_disabled() {
+    assert_eq!(Instr::decode(&[0xFD, 0x85, 0x02]), Err(DecodeError::Error));
+}
+
+#[cfg(feature = "fp16")]
+#[test]
+fn test_fp16_instr_decode() {
+    let bytes = [0xFD, 0xA1, 0x02, 0x07]; // f16x8.extract_lane 7
+    let (instr, rest) = Instr::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+    assert_eq!(instr, Instr::F16x8ExtractLane(LaneIdx(7)));
+}
+
+#[test]
+fn test_component_decode() {
+    use crate::component::*;
+
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6D, 0x0D, 0x00, 0x01, 0x00, // component header
+        0x01, 0x08, // core module section, size = 8
+        0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, // empty module
+        0x07, 0x05, // type section, size = 5
+        0x01, 0x40, 0x00, 0x01, 0x00, // (func)
+        0x0A, 0x06, // import section, size = 6
+        0x01, 0x00, 0x01, 0x66, 0x01, 0x00, // (import "f" (func (type 0)))
+    ];
+
+    let (binary, rest) = Binary::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+    assert_eq!(
+        binary,
+        Binary::Component(Component(Repeated(vec![
+            Section::CoreModule(Sized(Module(Repeated(vec![])))),
+            Section::Type(Sized(vec![Type::Func(vec![], ResultList::Empty())])),
+            Section::Import(Sized(vec![Import(
+                ExternName(Name("f".to_owned())),
+                ExternDesc::Func(TypeIdx(0))
+            )])),
+        ])))
+    );
+
+    let mut buffer = vec![];
+    binary.encode(&mut buffer);
+    assert_eq!(buffer, bytes);
+
+    let (binary, rest) = Binary::decode(&bytes[10..18]).unwrap();
+    assert_eq!(rest, []);
+    assert_eq!(binary, Binary::Module(Module(Repeated(vec![]))));
+
+    // Nested component
+    let mut nested = bytes[..8].to_vec();
+    nested.extend_from_slice(&[0x04, bytes.len() as u8]);
+    nested.extend_from_slice(&bytes);
+    let (component, rest) = Component::decode(&nested).unwrap();
+    assert_eq!(rest, []);
+
+    let mut buffer = vec![];
+    component.encode(&mut buffer);
+    assert_eq!(buffer, nested);
+    assert_eq!(component.encoded_len(), nested.len());
+}
+
+#[test]
+fn test_component_val_type_decode() {
+    use crate::component::*;
+
+    assert_eq!(ValType::decode(&[0x05]), Ok((ValType::Type(5), &[][..])));
+
+    let (ty, rest) = ValType::decode(&[0xC0, 0x00]).unwrap();
+    assert_eq!(rest, []);
+    assert_eq!(ty, ValType::Type(64));
+
+    let mut buffer = vec![];
+    ty.encode(&mut buffer);
+    assert_eq!(buffer, [0xC0, 0x00]);
+
+    // Negative type index
+    assert_eq!(ValType::decode(&[0x40]), Err(DecodeError::Error));
+}
+
+#[test]
+fn test_component_optional_decode() {
+    use crate::component::*;
+
+    // (result bool (error))
+    let bytes = [0x6A, 0x01, 0x7F, 0x00];
+    let (ty, rest) = DefValType::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+    assert_eq!(
+        ty,
+        DefValType::Result(Some(ValType::Prim(PrimValType::Bool())), None)
+    );
+
+    let mut buffer = vec![];
+    ty.encode(&mut buffer);
+    assert_eq!(buffer, bytes);
+
+    // (export "f" (func 0)), without the optional type
+    let bytes = [0x00, 0x01, 0x66, 0x01, 0x00, 0x00];
+    let (export, rest) = Export::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+    assert!(matches!(export, Export(_, _, None)));
+
+    assert_eq!(
+        DefValType::decode(&[0x6A, 0x02, 0x7F, 0x00]),
+        Err(DecodeError::Error)
+    );
+}
+
+#[test]
+fn test_linking_section_decode() {
+    use crate::linking::*;
+
+    let bytes: &[u8] = &[
+        0x02, // version
+        0x08, 0x0D, // symbol table, size = 13
+        0x03, // 3 symbols
+        0x00, 0x00, 0x00, 0x01, 0x66, // defined function 0 "f"
+        0x00, 0x10, 0x01, // undefined function 1
+        0x01, 0x10, 0x01, 0x64, // undefined data "d"
+        0x06, 0x03, // init funcs, size = 3
+        0x01, 0x0A, 0x00, // priority 10, symbol 0
+    ];
+    let custom = Custom::from_contents(LINKING_SECTION_NAME, &Repeated(bytes.to_vec()));
+
+    let linking: Linking = custom.decode_contents().unwrap();
+    assert_eq!(
+        linking,
+        Linking(Repeated(vec![
+            LinkingSubsection::SymbolTable(Sized(vec![
+                SymInfo::Function(0x00, 0, Some(Name("f".to_owned()))),
+                SymInfo::Function(0x10, 1, None),
+                SymInfo::Data(0x10, Name("d".to_owned()), None),
+            ])),
+            LinkingSubsection::InitFuncs(Sized(vec![InitFunc(10, 0)])),
+        ]))
+    );
+
+    assert_eq!(
+        Custom::from_contents(LINKING_SECTION_NAME, &linking),
+        custom
+    );
+}
+
+#[test]
+fn test_resolve_code_relocs() {
+    use crate::linking::*;
+
+    let code_section = [
+        0x01, // 1 function
+        0x11, // size = 17
+        0x00, // no locals
+        0x10, 0x80, 0x80, 0x80, 0x80, 0x00, // call 0
+        0x02, 0x40, // block
+        0x41, 0x80, 0x80, 0x80, 0x80, 0x00, // i32.const 0
+        0x0B, // end
+        0x0B, // end
+    ];
+    let (codes, rest) = Vec::<Code>::decode(&code_section).unwrap();
+    assert_eq!(rest, []);
+
+    let relocs = [
+        RelocEntry::FunctionIndexLeb(4, 0),
+        RelocEntry::MemoryAddrSleb(12, 1, 0),
+        RelocEntry::TypeIndexLeb(2, 0),
+    ];
+
+    let Func(_, Expr(Repeated(instrs))) = &(codes[0].0).0;
+    let block_instrs = match &instrs[1] {
+        Instr::Block(_, block_instrs) => &block_instrs.0,
+        other => panic!("{:?}", other),
+    };
+
+    assert_eq!(
+        resolve_code_relocs(&code_section, &codes, &relocs).unwrap(),
+        vec![
+            Some(CodeRelocTarget {
+                func: 0,
+                instr: &instrs[0]
+            }),
+            Some(CodeRelocTarget {
+                func: 0,
+                instr: &block_instrs[0]
+            }),
+            None,
+        ]
+    );
+
+    // Block with a type index that doesn't fit in an i32
+    let code_section = [
+        0x01, // 1 function
+        0x0F, // size = 15
+        0x00, // no locals
+        0x02, 0x80, 0x80, 0x80, 0x80, 0x08, // block (type 2^31)
+        0x41, 0x80, 0x80, 0x80, 0x80, 0x00, // i32.const 0
+        0x0B, // end
+        0x0B, // end
+    ];
+    let (codes, rest) = Vec::<Code>::decode(&code_section).unwrap();
+    assert_eq!(rest, []);
+
+    let relocs = [
+        RelocEntry::TypeIndexLeb(4, 0),
+        RelocEntry::MemoryAddrSleb(10, 1, 0),
+    ];
+
+    let Func(_, Expr(Repeated(instrs))) = &(codes[0].0).0;
+    let block_instrs = match &instrs[0] {
+        Instr::Block(BlockType::Type(0x8000_0000), block_instrs) => &block_instrs.0,
+        other => panic!("{:?}", other),
+    };
+
+    assert_eq!(
+        resolve_code_relocs(&code_section, &codes, &relocs).unwrap(),
+        vec![
+            Some(CodeRelocTarget {
+                func: 0,
+                instr: &instrs[0]
+            }),
+            Some(CodeRelocTarget {
+                func: 0,
+                instr: &block_instrs[0]
+            }),
+        ]
+    );
+}
+
+#[test]
+fn test_dylink_section_decode() {
+    use crate::dylink::*;
+
+    let bytes: &[u8] = &[
+        0x01, 0x04, // memory info, size = 4
+        0x10, 0x02, 0x01, 0x00, // memory size 16, align 2, table size 1, align 0
+        0x02, 0x06, // needed, size = 6
+        0x01, 0x04, 0x6C, 0x69, 0x62, 0x63, // "libc"
+    ];
+    let custom = Custom::from_contents(DYLINK_SECTION_NAME, &Repeated(bytes.to_vec()));
+
+    let mut dylink: Dylink = custom.decode_contents().unwrap();
+    assert_eq!(
+        dylink,
+        Dylink(Repeated(vec![
+            DylinkSubsection::MemInfo(Sized(MemInfo(16, 2, 1, 0))),
+            DylinkSubsection::Needed(Sized(vec![Name("libc".to_owned())])),
+        ]))
+    );
+
+    dylink.mem_info_mut().unwrap().0 = 1024;
+
+    let mut expected = bytes.to_vec();
+    expected.splice(1..3, [0x05, 0x80, 0x08]);
+    assert_eq!(
+        Custom::from_contents(DYLINK_SECTION_NAME, &dylink),
+        Custom::from_contents(DYLINK_SECTION_NAME, &Repeated(expected))
+    );
+}
+
+#[test]
+fn test_module_decode_strict() {
+    let header = [0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+    let type_section = [0x01, 0x01, 0x00];
+    let function_section = [0x03, 0x01, 0x00];
+    let code_section = [0x0A, 0x01, 0x00];
+    let custom_section = [0x00, 0x02, 0x01, 0x61];
+
+    let module = |sections: &[&[u8]]| -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        for section in sections {
+            bytes.extend_from_slice(section);
+        }
+        bytes
+    };
+
+    let bytes = module(&[
+        &custom_section,
+        &type_section,
+        &custom_section,
+        &function_section,
+        &code_section,
+        &custom_section,
+    ]);
+    let decoded = Module::decode_strict(&bytes).unwrap();
+    assert_eq!((decoded.0).0.len(), 6);
+
+    assert_eq!(
+        Module::decode_strict(&module(&[&type_section, &type_section])),
+        Err(DecodeError::DuplicateSection {
+            section: "type",
+            offset: 11,
+        })
+    );
+
+    assert_eq!(
+        Module::decode_strict(&module(&[&type_section, &code_section, &function_section])),
+        Err(DecodeError::SectionOutOfOrder {
+            section: "function",
+            offset: 14,
+        })
+    );
+
+    // Relaxed decoding accepts both
+    let bytes = module(&[&type_section, &code_section, &function_section]);
+    let (_, rest) = Module::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+}
+
+#[cfg(all(feature = "simd", feature = "bulk-memory"))]
+#[test]
+fn test_wasm_features() {
+    let mut mvp = DecodeContext::default();
+    mvp.features = WasmFeatures::mvp();
+
+    let i8x16_splat = [0xFD, 0x0F];
+    assert!(Instr::decode(&i8x16_splat).is_ok());
+    assert_eq!(
+        Instr::decode_with(&i8x16_splat, &mut mvp),
+        Err(DecodeError::FeatureNotEnabled("simd"))
+    );
+
+    assert_eq!(
+        ValType::decode_with(&[0x7B], &mut mvp),
+        Err(DecodeError::FeatureNotEnabled("simd"))
+    );
+    assert!(ValType::decode_with(&[0x7F], &mut mvp).is_ok());
+
+    let data_count_section = [0x0C, 0x01, 0x00];
+    assert!(Section::decode(&data_count_section).is_ok());
+    assert_eq!(
+        Section::decode_with(&data_count_section, &mut mvp),
+        Err(DecodeError::FeatureNotEnabled("bulk-memory"))
+    );
+
+    // Nested in a function body
+    let code_section = [
+        0x0A, 0x07, 0x01, // code section with one function
+        0x05, 0x00, 0xFD, 0x0F, 0x1A, 0x0B, // (drop (i8x16.splat))
+    ];
+    assert!(Section::decode(&code_section).is_ok());
+    assert_eq!(
+        Section::decode_with(&code_section, &mut mvp),
+        Err(DecodeError::FeatureNotEnabled("simd"))
+    );
+
+    let mut simd = DecodeContext::default();
+    simd.features = WasmFeatures {
+        simd: true,
+        ..WasmFeatures::mvp()
+    };
+    assert!(Section::decode_with(&code_section, &mut simd).is_ok());
+
+    // Proposals are only enabled with the proposals they depend on
+    #[cfg(feature = "fp16")]
+    {
+        let mut fp16 = DecodeContext::default();
+        fp16.features = WasmFeatures {
+            fp16: true,
+            ..WasmFeatures::mvp()
+        };
+        let f16x8_splat = [0xFD, 0xA0, 0x02];
+        assert!(Instr::decode(&f16x8_splat).is_ok());
+        assert_eq!(
+            Instr::decode_with(&f16x8_splat, &mut fp16),
+            Err(DecodeError::FeatureNotEnabled("fp16"))
+        );
+        assert!(!fp16.features.is_enabled("fp16"));
+        assert!(WasmFeatures::default().is_enabled("fp16"));
+    }
+}
+
+#[cfg(feature = "reference-types")]
+#[test]
+fn test_reference_types_feature() {
+    let mut mvp = DecodeContext::default();
+    mvp.features = WasmFeatures::mvp();
+
+    let instrs: [&[u8]; 6] = [
+        &[0x1C, 0x01, 0x7F], // select i32
+        &[0xD0, 0x70],       // ref.null func
+        &[0xD1],             // ref.is_null
+        &[0xD2, 0x00],       // ref.func 0
+        &[0x25, 0x00],       // table.get 0
+        &[0xFC, 0x10, 0x00], // table.size 0
+    ];
+    for instr in instrs {
+        assert!(Instr::decode(instr).is_ok());
+        assert_eq!(
+            Instr::decode_with(instr, &mut mvp),
+            Err(DecodeError::FeatureNotEnabled("reference-types"))
+        );
+    }
+
+    // Element segment with expressions and a reference type
+    let elem = [0x05, 0x70, 0x01, 0xD2, 0x00, 0x0B];
+    assert!(Elem::decode(&elem).is_ok());
+    assert_eq!(
+        Elem::decode_with(&elem, &mut mvp),
+        Err(DecodeError::FeatureNotEnabled("reference-types"))
+    );
+
+    assert_eq!(
+        ValType::decode_with(&[0x6F], &mut mvp),
+        Err(DecodeError::FeatureNotEnabled("reference-types"))
+    );
+}
+
+#[test]
+fn test_decode_limits() {
+    // Vector claiming 4 billion elements
+    assert_eq!(
+        Vec::<u32>::decode(&[0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 0x00]),
+        Err(DecodeError::Error)
+    );
+
+    let limits = DecodeLimits {
+        max_functions: 1,
+        max_locals: 10,
+        max_nesting_depth: 10,
+        max_section_size: 8,
+        max_input_size: 100,
+    };
+
+    let function_section = [0x03, 0x03, 0x02, 0x00, 0x00];
+    assert!(Section::decode(&function_section).is_ok());
+    assert_eq!(
+        Section::decode_with(&function_section, &mut DecodeContext::new(limits)),
+        Err(DecodeError::LimitExceeded("max_functions"))
+    );
+
+    // Checked from the vector lengths, before decoding the missing elements
+    let function_section = [0x03, 0x01, 0x02];
+    assert_eq!(
+        Section::decode_with(&function_section, &mut DecodeContext::new(limits)),
+        Err(DecodeError::LimitExceeded("max_functions"))
+    );
+    let code_section = [0x0A, 0x01, 0x02];
+    assert_eq!(
+        Section::decode_with(&code_section, &mut DecodeContext::new(limits)),
+        Err(DecodeError::LimitExceeded("max_functions"))
+    );
+
+    let custom_section = [
+        0x00, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+    assert!(Section::decode(&custom_section).is_ok());
+    assert_eq!(
+        Section::decode_with(&custom_section, &mut DecodeContext::new(limits)),
+        Err(DecodeError::LimitExceeded("max_section_size"))
+    );
+
+    // 6 + 5 locals
+    let func = [0x02, 0x06, 0x7F, 0x05, 0x7E, 0x0B];
+    assert!(Func::decode_with(
+        &func,
+        &mut DecodeContext::new(DecodeLimits {
+            max_locals: 11,
+            ..limits
+        })
+    )
+    .is_ok());
+    assert_eq!(
+        Func::decode_with(&func, &mut DecodeContext::new(limits)),
+        Err(DecodeError::LimitExceeded("max_locals"))
+    );
+
+    // Expression with `depth` nested blocks
+    let nested_blocks = |depth: usize| {
+        let mut expr = vec![];
+        for _ in 0..depth {
+            expr.extend_from_slice(&[0x02, 0x40]);
+        }
+        expr.resize(expr.len() + depth + 1, 0x0B);
+        expr
+    };
+    assert!(Expr::decode_with(&nested_blocks(10), &mut DecodeContext::new(limits)).is_ok());
+    assert_eq!(
+        Expr::decode_with(&nested_blocks(11), &mut DecodeContext::new(limits)),
+        Err(DecodeError::LimitExceeded("max_nesting_depth"))
+    );
+}
+
+#[test]
+fn test_deeply_nested_blocks() {
+    const DEPTH: usize = 100_000;
+
+    let mut bytes = vec![];
+    for _ in 0..DEPTH {
+        bytes.extend_from_slice(&[0x02, 0x40]);
+    }
+    bytes.resize(bytes.len() + DEPTH + 1, 0x0B);
+
+    assert_eq!(
+        Expr::decode(&bytes),
+        Err(DecodeError::LimitExceeded("max_nesting_depth"))
+    );
+
+    let max_depth = DecodeLimits::default().max_nesting_depth;
+    assert!(Expr::decode(&bytes[DEPTH * 2 - max_depth * 2..DEPTH * 2 + max_depth + 1]).is_ok());
+
+    // Encoding does not recurse
+    let mut instrs = vec![];
+    for _ in 0..DEPTH {
+        instrs = vec![Instr::Block(BlockType::Empty, Repeated(instrs))];
+    }
+    let mut expr = Expr(Repeated(instrs));
+
+    let mut buffer = vec![];
+    expr.encode(&mut buffer);
+    assert_eq!(buffer, bytes);
+    assert_eq!(expr.encoded_len(), bytes.len());
+
+    // Dropping recurses, so take the blocks apart
+    while let Some(Instr::Block(_, Repeated(instrs))) = (expr.0).0.pop() {
+        (expr.0).0 = instrs;
+    }
+}
+
+#[test]
+fn test_nested_instr_encode() {
+    let bytes = [
+        0x04, 0x40, // if
+        0x02, 0x40, 0x41, 0x01, 0x0B, // block (i32.const 1) end
+        0x05, // else
+        0x03, 0x40, 0x0C, 0x00, 0x0B, // loop (br 0) end
+        0x0B, // end
+        0x0B, // end of expr
+    ];
+    let (expr, rest) = Expr::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+
+    let mut buffer = vec![];
+    expr.encode(&mut buffer);
+    assert_eq!(buffer, bytes);
+}
+
+#[test]
+fn test_operators() {
+    use crate::operators::Operator;
+
+    let bytes = [
+        0x04, 0x40, // if
+        0x02, 0x40, 0x41, 0x01, 0x1A, 0x0B, // block (drop (i32.const 1)) end
+        0x05, // else
+        0x03, 0x40, 0x0C, 0x00, 0x0B, // loop (br 0) end
+        0x0B, // end
+        0x04, 0x40, 0x01, 0x0B, // if nop end
+        0x0B, // end of expr
+    ];
+    let (expr, rest) = Expr::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+
+    let operators = expr.into_operators();
+    assert_eq!(
+        operators,
+        vec![
+            Operator::If(BlockType::Empty),
+            Operator::Block(BlockType::Empty),
+            Operator::Instr(Instr::I32Const(1)),
+            Operator::Instr(Instr::Drop()),
+            Operator::End,
+            Operator::Else,
+            Operator::Loop(BlockType::Empty),
+            Operator::Instr(Instr::Br(LabelIdx(0))),
+            Operator::End,
+            Operator::End,
+            Operator::If(BlockType::Empty),
+            Operator::Instr(Instr::Nop()),
+            Operator::End,
+            Operator::End,
+        ]
+    );
+
+    let mut buffer = vec![];
+    for operator in &operators {
+        operator.encode(&mut buffer);
+    }
+    assert_eq!(buffer, bytes);
+
+    let (decoded, rest): (Repeated<Operator>, _) = Repeated::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+    assert_eq!(decoded.0, operators);
+
+    let expr = Expr::from_operators(operators).unwrap();
+    let mut buffer = vec![];
+    expr.encode(&mut buffer);
+    assert_eq!(buffer, bytes);
+
+    // Unbalanced
+    assert!(Expr::from_operators(vec![Operator::Block(BlockType::Empty), Operator::End]).is_err());
+    assert!(Expr::from_operators(vec![Operator::Else, Operator::End]).is_err());
+    assert!(Expr::from_operators(vec![Operator::End, Operator::End]).is_err());
+}
+
+#[test]
+fn test_operators_reader() {
+    let code = [
+        0x12, // size = 18
+        0x01, 0x02, 0x7F, // 2 i32 locals
+        0x10, 0x03, // call 3
+        0x02, 0x40, // block
+        0x41, 0x00, // i32.const 0
+        0x0E, 0x02, 0x00, 0x01, 0x00, // br_table 0 1 0
+        0x0B, // end
+        0x10, 0x05, // call 5
+        0x0B, // end
+    ];
+
+    let operators: Vec<(usize, InstrRef)> = OperatorsReader::from_code(&code)
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    let offsets: Vec<usize> = operators.iter().map(|(offset, _)| *offset).collect();
+    assert_eq!(offsets, [4, 6, 8, 10, 15, 16, 18]);
+
+    let calls: Vec<u32> = operators
+        .iter()
+        .filter_map(|(_, operator)| match operator {
+            InstrRef::Call(FuncIdx(f)) => Some(*f),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(calls, [3, 5]);
+
+    match &operators[3].1 {
+        InstrRef::BrTable(labels, default) => {
+            assert_eq!(labels.len(), 2);
+            assert_eq!(labels.collect::<Vec<_>>(), [LabelIdx(0), LabelIdx(1)]);
+            assert_eq!(*default, LabelIdx(0));
+        }
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(operators[4].1, InstrRef::End());
+
+    // Same operators as the AST
+    let (code, _) = Code::decode(&code).unwrap();
+    assert_eq!(((code.0).0).1.into_operators().len(), operators.len());
+
+    // Errors are reported once
+    let mut reader = OperatorsReader::new(&[0x01, 0xFF, 0x01], 0);
+    assert_eq!(reader.next(), Some(Ok((0, InstrRef::Nop()))));
+    assert_eq!(reader.next(), Some(Err(DecodeError::Error)));
+    assert_eq!(reader.next(), None);
+
+    // Proposals disabled in the context are rejected, as in `Instr::decode_with`
+    #[cfg(feature = "simd")]
+    {
+        let i8x16_splat = [0xFD, 0x0F];
+        let mut ctx = DecodeContext {
+            features: WasmFeatures::mvp(),
+            ..DecodeContext::default()
+        };
+        assert!(InstrRef::read(&i8x16_splat).is_ok());
+        assert_eq!(
+            InstrRef::read_with(&i8x16_splat, &mut ctx),
+            Err(DecodeError::FeatureNotEnabled("simd"))
+        );
+
+        let mut reader = OperatorsReader::new(&i8x16_splat, 0).with_context(ctx);
+        assert_eq!(
+            reader.next(),
+            Some(Err(DecodeError::FeatureNotEnabled("simd")))
+        );
+    }
+}
+
+#[test]
+fn test_code_section() {
+    let mut bytes = vec![0x40]; // 64 functions
+    for i in 0..0x40 {
+        bytes.extend_from_slice(&[0x04, 0x00, 0x41, i, 0x0B]); // (i32.const i)
+    }
+
+    let (CodeSection(codes), rest) = CodeSection::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+    assert_eq!(codes.len(), 0x40);
+    assert_eq!(
+        codes[0x3F],
+        Code(Sized(Func(
+            vec![],
+            Expr(Repeated(vec![Instr::I32Const(0x3F)]))
+        )))
+    );
+
+    let mut buffer = vec![];
+    CodeSection(codes).encode(&mut buffer);
+    assert_eq!(buffer, bytes);
+
+    // Errors are the same as decoding the entries in order
+    let mut invalid = bytes.clone();
+    invalid[1 + 5 * 10 + 2] = 0xFF; // invalid instruction in function 10
+    invalid[1 + 5 * 20] = 0x7F; // size of function 20 is larger than the section
+    assert_eq!(CodeSection::decode(&invalid), Err(DecodeError::Error));
+
+    let limits = DecodeLimits {
+        max_locals: 1,
+        max_section_size: 0x10,
+        ..Default::default()
+    };
+    let mut invalid = bytes.clone();
+    invalid.splice(1 + 5 * 10..1 + 5 * 10 + 2, [0x06, 0x01, 0x02, 0x7F]); // 2 locals
+    invalid[1 + 5 * 20 + 2] = 0x7F;
+    assert_eq!(
+        CodeSection::decode_with(&invalid, &mut DecodeContext::new(limits)),
+        Err(DecodeError::LimitExceeded("max_locals"))
+    );
+}
+
+#[test]
+fn test_encoded_len() {
+    fn check<T: Encode>(value: T) {
+        let mut buffer = vec![];
+        value.encode(&mut buffer);
+        assert_eq!(value.encoded_len(), buffer.len());
+    }
+
+    for shift in 0..64 {
+        for delta in [-1i64, 0, 1] {
+            let value = (1i64 << shift).wrapping_add(delta);
+            check(value);
+            check(value.wrapping_neg());
+            check(value as u64);
+            check(value as i32);
+            check((value as i32).wrapping_neg());
+            check(value as u32);
+        }
+    }
+    check(i32::MIN);
+    check(i64::MIN);
+    check(u32::MAX);
+    check(u64::MAX);
+
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+        0x03, 0x02, 0x01, 0x00, // function section
+        0x0A, 0x0A, 0x01, // code section
+        0x08, 0x01, 0x01, 0x7F, // 1 local
+        0x41, 0xC0, 0x00, 0x1A, 0x0B, // (drop (i32.const 64))
+        0x00, 0x05, 0x04, 0x6E, 0x61, 0x6D, 0x65, // custom section
+    ];
+    let (module, rest) = Module::decode(&bytes).unwrap();
+    assert_eq!(rest, []);
+    assert_eq!(module.encoded_len(), bytes.len());
+
+    let mut buffer = vec![];
+    module.encode(&mut buffer);
+    assert_eq!(buffer, bytes);
+}
+
+#[test]
+fn test_encode_to_slice() {
+    let instrs = Expr(Repeated(vec![Instr::I32Const(-1), Instr::Drop()]));
+    let mut bytes = [0u8; 4];
+
+    let mut writer: &mut [u8] = &mut bytes;
+    instrs.encode_to(&mut writer).unwrap();
+    assert!(writer.is_empty());
+    assert_eq!(bytes, [0x41, 0x7F, 0x1A, 0x0B]);
+
+    let mut short = [0u8; 2];
+    assert!(instrs.encode_to(&mut &mut short[..]).is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_encode_to_decode_from() {
+    use std::io;
+
+    let bytes = [
+        0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+        0x03, 0x02, 0x01, 0x00, // function section
+        0x0A, 0x04, 0x01, 0x02, 0x00, 0x0B, // code section
+    ];
+
+    let module = Module::decode_from(&mut io::Cursor::new(&bytes)).unwrap();
+
+    let mut writer = io::Cursor::new(vec![]);
+    module.encode_to(&mut writer).unwrap();
+    assert_eq!(writer.into_inner(), bytes);
+
+    // Trailing bytes
+    let mut trailing = bytes.to_vec();
+    trailing.push(0x00);
+    assert!(matches!(
+        Module::decode_from(&mut trailing.as_slice()),
+        Err(ReadError::Decode(DecodeError::Error))
+    ));
+
+    // I/O errors are propagated
+    struct Failing(usize);
+
+    impl io::Write for Failing {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.0 < buf.len() {
+                return Err(io::Error::other("disk full"));
+            }
+            self.0 -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl io::Read for Failing {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("broken pipe"))
+        }
+    }
+
+    assert_eq!(
+        module.encode_to(&mut Failing(10)).unwrap_err().to_string(),
+        "disk full"
+    );
+    assert!(module.encode_to(&mut Failing(bytes.len())).is_ok());
+    assert!(matches!(
+        Module::decode_from(&mut Failing(0)),
+        Err(ReadError::Io(_))
+    ));
+
+    // Reading stops at the input size limit, even for endless readers
+    let mut ctx = DecodeContext::new(DecodeLimits {
+        max_input_size: bytes.len(),
+        ..DecodeLimits::default()
+    });
+    assert!(Module::decode_from_with(&mut bytes.as_slice(), &mut ctx).is_ok());
+    assert!(matches!(
+        Module::decode_from_with(&mut trailing.as_slice(), &mut ctx),
+        Err(ReadError::Decode(DecodeError::LimitExceeded(
+            "max_input_size"
+        )))
+    ));
+    assert!(matches!(
+        Module::decode_from_with(&mut io::repeat(0), &mut ctx),
+        Err(ReadError::Decode(DecodeError::LimitExceeded(
+            "max_input_size"
+        )))
+    ));
+}
@@ -131,7 +131,7 @@ impl Encode for Name {
     }
 }
 impl Decode for u8 {
-    fn decode(buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode(buffer: &[u8]) -> DecodeResult<'_, Self> {
         match buffer.first() {
             Some(byte) => Ok((*byte, &buffer[1..])),
             None => Err(DecodeError::Error),
@@ -140,7 +140,7 @@ impl Decode for u8 {
 }
 #[doc = " Unsigned LEB128"]
 impl Decode for u32 {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Self> {
         let mut result: u32 = 0;
         let mut shift: u32 = 0;
         loop {
@@ -163,7 +163,7 @@ impl Decode for u32 {
 }
 #[doc = " Signed LEB128"]
 impl Decode for i32 {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Self> {
         let mut result: u32 = 0;
         let mut shift: u32 = 0;
         let mut byte;
@@ -187,7 +187,7 @@ impl Decode for i32 {
 }
 #[doc = " Unsigned LEB128"]
 impl Decode for u64 {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Self> {
         let mut result: u64 = 0;
         let mut shift: u32 = 0;
         loop {
@@ -210,7 +210,7 @@ impl Decode for u64 {
 }
 #[doc = " Unsigned LEB128"]
 impl Decode for i64 {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Self> {
         let mut result: u64 = 0;
         let mut shift: u32 = 0;
         let mut byte;
@@ -233,7 +233,7 @@ impl Decode for i64 {
     }
 }
 impl Decode for f32 {
-    fn decode(buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode(buffer: &[u8]) -> DecodeResult<'_, Self> {
         let b1 = buffer[0];
         let b2 = buffer[1];
         let b3 = buffer[2];
@@ -242,7 +242,7 @@ impl Decode for f32 {
     }
 }
 impl Decode for f64 {
-    fn decode(buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode(buffer: &[u8]) -> DecodeResult<'_, Self> {
         let b1 = buffer[0];
         let b2 = buffer[1];
         let b3 = buffer[2];
@@ -258,7 +258,7 @@ impl Decode for f64 {
     }
 }
 impl<T: Decode> Decode for Vec<T> {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Self> {
         let (length, buffer_) = u32::decode(buffer)?;
         buffer = buffer_;
         let mut vec: Vec<T> = Vec::with_capacity(length as usize);
@@ -271,7 +271,7 @@ impl<T: Decode> Decode for Vec<T> {
     }
 }
 impl<T: Decode> Decode for Repeated<T> {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Self> {
         let mut stuff = Vec::new();
         while let Ok((thing, buffer_)) = T::decode(buffer) {
             buffer = buffer_;
@@ -281,7 +281,7 @@ impl<T: Decode> Decode for Repeated<T> {
     }
 }
 impl<T: Decode> Decode for Sized<T> {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Self> {
         let (size, buffer_) = u32::decode(buffer)?;
         let size = size as usize;
         buffer = buffer_;
@@ -294,7 +294,7 @@ impl<T: Decode> Decode for Sized<T> {
     }
 }
 impl Decode for Name {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Self> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Self> {
         let (length, buffer_) = u32::decode(buffer)?;
         let length = length as usize;
         buffer = buffer_;
@@ -320,7 +320,7 @@ impl Encode for Module {
     }
 }
 impl Decode for Module {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Module> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Module> {
         let (lit, buffer_) = u8::decode(buffer)?;
         if lit != 0u8 {
             return Err(DecodeError::Error);
@@ -441,7 +441,7 @@ impl Encode for Section {
     }
 }
 impl Decode for Section {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Section> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Section> {
         match buffer {
             [0u8, ..] => {
                 buffer = &buffer[1usize..];
@@ -535,7 +535,7 @@ impl Encode for Custom {
     }
 }
 impl Decode for Custom {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Custom> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Custom> {
         let (name, buffer_) = Name::decode(buffer)?;
         buffer = buffer_;
         let (bytes, buffer_) = Repeated::<u8>::decode(buffer)?;
@@ -554,7 +554,7 @@ impl Encode for FuncType {
     }
 }
 impl Decode for FuncType {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<FuncType> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, FuncType> {
         let (lit, buffer_) = u8::decode(buffer)?;
         if lit != 96u8 {
             return Err(DecodeError::Error);
@@ -576,7 +576,7 @@ impl Encode for ResultType {
     }
 }
 impl Decode for ResultType {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<ResultType> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, ResultType> {
         let (tys, buffer_) = Vec::<ValType>::decode(buffer)?;
         buffer = buffer_;
         Ok((ResultType(tys), buffer))
@@ -620,7 +620,7 @@ impl Encode for ValType {
     }
 }
 impl Decode for ValType {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<ValType> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, ValType> {
         match buffer {
             [127u8, ..] => {
                 buffer = &buffer[1usize..];
@@ -680,7 +680,7 @@ impl Encode for NumType {
     }
 }
 impl Decode for NumType {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<NumType> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, NumType> {
         match buffer {
             [127u8, ..] => {
                 buffer = &buffer[1usize..];
@@ -720,7 +720,7 @@ impl Encode for RefType {
     }
 }
 impl Decode for RefType {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<RefType> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, RefType> {
         match buffer {
             [112u8, ..] => {
                 buffer = &buffer[1usize..];
@@ -743,7 +743,7 @@ impl Encode for VecType {
     }
 }
 impl Decode for VecType {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<VecType> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, VecType> {
         let (lit, buffer_) = u8::decode(buffer)?;
         if lit != 123u8 {
             return Err(DecodeError::Error);
@@ -763,7 +763,7 @@ impl Encode for Import {
     }
 }
 impl Decode for Import {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Import> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Import> {
         let (module, buffer_) = Name::decode(buffer)?;
         buffer = buffer_;
         let (import_name, buffer_) = Name::decode(buffer)?;
@@ -803,7 +803,7 @@ impl Encode for ImportDesc {
     }
 }
 impl Decode for ImportDesc {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<ImportDesc> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, ImportDesc> {
         match buffer {
             [0u8, ..] => {
                 buffer = &buffer[1usize..];
@@ -843,7 +843,7 @@ impl Encode for TableType {
     }
 }
 impl Decode for TableType {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<TableType> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, TableType> {
         let (et, buffer_) = RefType::decode(buffer)?;
         buffer = buffer_;
         let (lim, buffer_) = Limits::decode(buffer)?;
@@ -860,7 +860,7 @@ impl Encode for MemType {
     }
 }
 impl Decode for MemType {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<MemType> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, MemType> {
         let (lim, buffer_) = Limits::decode(buffer)?;
         buffer = buffer_;
         Ok((MemType(lim), buffer))
@@ -876,7 +876,7 @@ impl Encode for GlobalType {
     }
 }
 impl Decode for GlobalType {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<GlobalType> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, GlobalType> {
         let (t, buffer_) = ValType::decode(buffer)?;
         buffer = buffer_;
         let (m, buffer_) = Mut::decode(buffer)?;
@@ -905,7 +905,7 @@ impl Encode for Limits {
     }
 }
 impl Decode for Limits {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Limits> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Limits> {
         match buffer {
             [0u8, ..] => {
                 buffer = &buffer[1usize..];
@@ -943,7 +943,7 @@ impl Encode for Mut {
     }
 }
 impl Decode for Mut {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Mut> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Mut> {
         match buffer {
             [0u8, ..] => {
                 buffer = &buffer[1usize..];
@@ -966,7 +966,7 @@ impl Encode for Table {
     }
 }
 impl Decode for Table {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Table> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Table> {
         let (tt, buffer_) = TableType::decode(buffer)?;
         buffer = buffer_;
         Ok((Table(tt), buffer))
@@ -981,7 +981,7 @@ impl Encode for Mem {
     }
 }
 impl Decode for Mem {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Mem> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Mem> {
         let (mt, buffer_) = MemType::decode(buffer)?;
         buffer = buffer_;
         Ok((Mem(mt), buffer))
@@ -997,7 +997,7 @@ impl Encode for Global {
     }
 }
 impl Decode for Global {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Global> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Global> {
         let (gt, buffer_) = GlobalType::decode(buffer)?;
         buffer = buffer_;
         let (e, buffer_) = Expr::decode(buffer)?;
@@ -1015,7 +1015,7 @@ impl Encode for Export {
     }
 }
 impl Decode for Export {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Export> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Export> {
         let (nm, buffer_) = Name::decode(buffer)?;
         buffer = buffer_;
         let (d, buffer_) = ExportDesc::decode(buffer)?;
@@ -1053,7 +1053,7 @@ impl Encode for ExportDesc {
     }
 }
 impl Decode for ExportDesc {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<ExportDesc> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, ExportDesc> {
         match buffer {
             [0u8, ..] => {
                 buffer = &buffer[1usize..];
@@ -1145,7 +1145,7 @@ impl Encode for Elem {
     }
 }
 impl Decode for Elem {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Elem> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Elem> {
         match buffer {
             [0u8, ..] => {
                 buffer = &buffer[1usize..];
@@ -1232,7 +1232,7 @@ impl Encode for ElemKind {
     }
 }
 impl Decode for ElemKind {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<ElemKind> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, ElemKind> {
         let (lit, buffer_) = u8::decode(buffer)?;
         if lit != 0u8 {
             return Err(DecodeError::Error);
@@ -1250,7 +1250,7 @@ impl Encode for Code {
     }
 }
 impl Decode for Code {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Code> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Code> {
         let (code, buffer_) = Sized::<Func>::decode(buffer)?;
         buffer = buffer_;
         Ok((Code(code), buffer))
@@ -1266,7 +1266,7 @@ impl Encode for Func {
     }
 }
 impl Decode for Func {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Func> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Func> {
         let (locals, buffer_) = Vec::<Locals>::decode(buffer)?;
         buffer = buffer_;
         let (e, buffer_) = Expr::decode(buffer)?;
@@ -1284,7 +1284,7 @@ impl Encode for Locals {
     }
 }
 impl Decode for Locals {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Locals> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Locals> {
         let (n, buffer_) = u32::decode(buffer)?;
         buffer = buffer_;
         let (t, buffer_) = ValType::decode(buffer)?;
@@ -1320,7 +1320,7 @@ impl Encode for Data {
     }
 }
 impl Decode for Data {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Data> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Data> {
         match buffer {
             [0u8, ..] => {
                 buffer = &buffer[1usize..];
@@ -1359,7 +1359,7 @@ impl Encode for TypeIdx {
     }
 }
 impl Decode for TypeIdx {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<TypeIdx> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, TypeIdx> {
         let (x, buffer_) = u32::decode(buffer)?;
         buffer = buffer_;
         Ok((TypeIdx(x), buffer))
@@ -1374,7 +1374,7 @@ impl Encode for TableIdx {
     }
 }
 impl Decode for TableIdx {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<TableIdx> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, TableIdx> {
         let (x, buffer_) = u32::decode(buffer)?;
         buffer = buffer_;
         Ok((TableIdx(x), buffer))
@@ -1389,7 +1389,7 @@ impl Encode for FuncIdx {
     }
 }
 impl Decode for FuncIdx {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<FuncIdx> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, FuncIdx> {
         let (x, buffer_) = u32::decode(buffer)?;
         buffer = buffer_;
         Ok((FuncIdx(x), buffer))
@@ -1404,7 +1404,7 @@ impl Encode for MemIdx {
     }
 }
 impl Decode for MemIdx {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<MemIdx> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, MemIdx> {
         let (x, buffer_) = u32::decode(buffer)?;
         buffer = buffer_;
         Ok((MemIdx(x), buffer))
@@ -1419,7 +1419,7 @@ impl Encode for GlobalIdx {
     }
 }
 impl Decode for GlobalIdx {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<GlobalIdx> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, GlobalIdx> {
         let (x, buffer_) = u32::decode(buffer)?;
         buffer = buffer_;
         Ok((GlobalIdx(x), buffer))
@@ -1434,7 +1434,7 @@ impl Encode for LabelIdx {
     }
 }
 impl Decode for LabelIdx {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<LabelIdx> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, LabelIdx> {
         let (x, buffer_) = u32::decode(buffer)?;
         buffer = buffer_;
         Ok((LabelIdx(x), buffer))
@@ -1449,7 +1449,7 @@ impl Encode for LocalIdx {
     }
 }
 impl Decode for LocalIdx {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<LocalIdx> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, LocalIdx> {
         let (x, buffer_) = u32::decode(buffer)?;
         buffer = buffer_;
         Ok((LocalIdx(x), buffer))
@@ -1464,7 +1464,7 @@ impl Encode for ElemIdx {
     }
 }
 impl Decode for ElemIdx {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<ElemIdx> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, ElemIdx> {
         let (x, buffer_) = u32::decode(buffer)?;
         buffer = buffer_;
         Ok((ElemIdx(x), buffer))
@@ -1479,7 +1479,7 @@ impl Encode for DataIdx {
     }
 }
 impl Decode for DataIdx {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<DataIdx> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, DataIdx> {
         let (x, buffer_) = u32::decode(buffer)?;
         buffer = buffer_;
         Ok((DataIdx(x), buffer))
@@ -1495,7 +1495,7 @@ impl Encode for Expr {
     }
 }
 impl Decode for Expr {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Expr> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Expr> {
         let (instrs, buffer_) = Repeated::<Instr>::decode(buffer)?;
         buffer = buffer_;
         let (lit, buffer_) = u8::decode(buffer)?;
@@ -2399,7 +2399,7 @@ impl Encode for Instr {
     }
 }
 impl Decode for Instr {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Instr> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Instr> {
         match buffer {
             [0u8, ..] => {
                 buffer = &buffer[1usize..];
@@ -3358,7 +3358,7 @@ impl Encode for Else {
     }
 }
 impl Decode for Else {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<Else> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, Else> {
         match buffer {
             [11u8, ..] => {
                 buffer = &buffer[1usize..];
@@ -3389,7 +3389,7 @@ impl Encode for MemArg {
     }
 }
 impl Decode for MemArg {
-    fn decode(mut buffer: &[u8]) -> DecodeResult<MemArg> {
+    fn decode(mut buffer: &[u8]) -> DecodeResult<'_, MemArg> {
         let (align, buffer_) = u32::decode(buffer)?;
         buffer = buffer_;
         let (offset, buffer_) = u32::decode(buffer)?;
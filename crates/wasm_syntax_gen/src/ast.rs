@@ -1,20 +1,74 @@
 use syn::parse::{Parse, ParseBuffer};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
 
 #[derive(Debug)]
 pub struct Grammar {
     pub non_terminals: Vec<NonTerminal>,
+
+    /// Types declared with `extern Foo;`
+    pub externs: Vec<Extern>,
 }
 
+/// A type declared with `extern Foo;`. These are defined outside of the grammar, with hand-written
+/// `Encode` and `Decode` impls.
+///
+/// `extern Foo: 0x01 | 0x02 0x03;` also declares the literals that encodings of the type start
+/// with, to dispatch on productions starting with the type as with non-terminals of the grammar.
+#[derive(Debug)]
+pub struct Extern {
+    pub name: syn::Ident,
+
+    /// Declared first literals. `None` if not declared, in which case the type can start with any
+    /// byte.
+    pub firsts: Option<Vec<Vec<Literal>>>,
+}
+
+/// Types defined by the runtime crate that can be used in grammars without an `extern` declaration.
+/// Integer and float types are parsed as builtins, so they don't need to be listed here.
+const PREDEFINED_TYPES: &[&str] = &["Name"];
+
+/// Largest tuple size supported by the runtime crate's `Encode` and `Decode` implementations.
+const MAX_TUPLE_LEN: usize = 4;
+
 #[derive(Debug)]
 pub struct NonTerminal {
     pub name: syn::Ident,
 
+    /// Operator reader to generate for the non-terminal, from a `#[reader(...)]` attribute.
+    pub reader: Option<Reader>,
+
     /// Non-empty list of productions
     pub productions: Vec<Production>,
 }
 
+/// `#[reader(Reader, Item, Marker = 0x0B, ...)]`: generates an iterator `Reader` that yields the
+/// non-terminal as `Item`s without building nested values.
+///
+/// Productions are cut at a `repeated(...)` of the non-terminal itself. The bytes that end the
+/// repetition are yielded as markers, which are field-less `Item` variants.
+#[derive(Debug)]
+pub struct Reader {
+    pub name: syn::Ident,
+    pub item: syn::Ident,
+    pub markers: Vec<(syn::Ident, u8)>,
+}
+
 #[derive(Debug)]
 pub struct Production {
+    /// `#[cfg(...)]` attributes of the production. These are copied to the generated enum variant
+    /// and its encoder and decoder. `#[feature = "..."]` attributes are desugared to
+    /// `#[cfg(feature = "...")]`.
+    pub attrs: Vec<syn::Attribute>,
+
+    /// Features from `#[feature = "..."]` attributes. Decoders check that these are enabled in the
+    /// decode context's `WasmFeatures`.
+    pub features: Vec<syn::LitStr>,
+
+    /// Functions from `#[check(...)]` attributes. These are called with the decoded value and the
+    /// decode context after decoding the production, to check implementation limits.
+    pub checks: Vec<syn::Path>,
+
     /// Non-empty list of symbols
     pub symbols: Vec<Symbol>,
 
@@ -75,16 +129,22 @@ impl Literal {
 /// A symbol bound to a name
 #[derive(Debug)]
 pub enum BoundSymbol {
-    /// A vector: `vec(<type>)`
-    Vec(syn::Ident),
+    /// A vector: `vec(<symbol>)`
+    Vec(Box<BoundSymbol>),
 
-    /// Zero or more things: `repeated(<type>)`. Unlike `Vec`, encoding of this type of fields do
-    /// not have length prefix.
-    Repeated(syn::Ident),
+    /// Zero or more things: `repeated(<symbol>)`. Unlike `Vec`, encoding of this type of fields do
+    /// not have length prefix. Only allowed as a field, or directly in `sized(...)`.
+    Repeated(Box<BoundSymbol>),
 
-    /// A sized section: `sized(<type>)`
+    /// A sized section: `sized(<symbol>)`
     Sized(Box<BoundSymbol>),
 
+    /// An optional value after a flag byte, 0x00 for none and 0x01 for some: `optional(<symbol>)`
+    Optional(Box<BoundSymbol>),
+
+    /// Two or more symbols one after another: `(<symbol>, <symbol>, ...)`
+    Tuple(Vec<BoundSymbol>),
+
     /// A built-in type: `name`, `u32` etc.
     Builtin(BuiltinSymbol),
 
@@ -92,26 +152,189 @@ pub enum BoundSymbol {
     Type(syn::Ident),
 }
 
+impl BoundSymbol {
+    /// Adds the non-builtin types in the symbol to `names`.
+    pub fn type_names<'a>(&'a self, names: &mut Vec<&'a syn::Ident>) {
+        match self {
+            BoundSymbol::Vec(nested)
+            | BoundSymbol::Repeated(nested)
+            | BoundSymbol::Sized(nested)
+            | BoundSymbol::Optional(nested) => nested.type_names(names),
+            BoundSymbol::Tuple(elems) => {
+                for elem in elems {
+                    elem.type_names(names);
+                }
+            }
+            BoundSymbol::Builtin(_) => {}
+            BoundSymbol::Type(ty) => names.push(ty),
+        }
+    }
+}
+
+/// Where a bound symbol appears, to check the symbols that can only be used in some places.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Nesting {
+    /// A field of a production
+    Field,
+
+    /// Directly in `sized(...)`
+    Sized,
+
+    /// In any other combinator
+    Nested,
+}
+
 #[derive(Debug)]
 pub enum BuiltinSymbol {
     Name,
+    U8,
     U32,
+    U64,
+    I32,
+    I64,
+
+    /// A non-negative signed 33-bit LEB-128 integer, as used for type indices: `s33_index`. Stored
+    /// as `u32`.
+    S33Index,
+
+    F32,
+    F64,
+
+    /// A fixed number of bytes without a length prefix: `bytes(N)`, stored as `[u8; N]`
+    Bytes(usize),
+
+    /// `N` lane indices into two vectors of `N` lanes, as in a shuffle mask: `lanes(N)`. Stored as
+    /// `[u8; N]`. Each index is checked to be less than `2 * N` when decoding.
+    Lanes(usize),
 }
 
 impl Parse for Grammar {
     fn parse(input: &ParseBuffer) -> syn::Result<Self> {
         let mut non_terminals = Vec::new();
+        let mut externs = Vec::new();
 
         while !input.is_empty() {
-            non_terminals.push(NonTerminal::parse(input)?);
+            if input.peek(syn::token::Extern) {
+                externs.push(Extern::parse(input)?);
+            } else {
+                non_terminals.push(NonTerminal::parse(input)?);
+            }
         }
 
-        Ok(Grammar { non_terminals })
+        let grammar = Grammar {
+            non_terminals,
+            externs,
+        };
+        grammar.check()?;
+        Ok(grammar)
+    }
+}
+
+impl Grammar {
+    /// Checks for duplicate definitions, duplicate field names and undefined types.
+    fn check(&self) -> syn::Result<()> {
+        let mut errors: Option<syn::Error> = None;
+        let mut add_error = |error: syn::Error| match &mut errors {
+            Some(errors) => errors.combine(error),
+            None => errors = Some(error),
+        };
+
+        let mut defined: Vec<&syn::Ident> = Vec::with_capacity(self.non_terminals.len());
+        for name in self
+            .non_terminals
+            .iter()
+            .map(|non_terminal| &non_terminal.name)
+            .chain(self.externs.iter().map(|extern_| &extern_.name))
+        {
+            if defined.contains(&name) {
+                add_error(syn::Error::new_spanned(
+                    name,
+                    format!("`{}` is defined multiple times", name),
+                ));
+            }
+            defined.push(name);
+        }
+
+        for non_terminal in &self.non_terminals {
+            let mut variants: Vec<&syn::Ident> = Vec::with_capacity(non_terminal.productions.len());
+
+            for production in &non_terminal.productions {
+                if non_terminal.productions.len() > 1 {
+                    if variants.contains(&&production.rhs) {
+                        add_error(syn::Error::new_spanned(
+                            &production.rhs,
+                            format!(
+                                "Variant `{}` is defined multiple times in `{}`",
+                                production.rhs, non_terminal.name
+                            ),
+                        ));
+                    }
+                    variants.push(&production.rhs);
+                }
+
+                let mut fields: Vec<&syn::Ident> = Vec::with_capacity(production.symbols.len());
+                for symbol in &production.symbols {
+                    let (field, bound_symbol) = match symbol {
+                        Symbol::Literal(_) => continue,
+                        Symbol::Bound(field, bound_symbol) => (field, bound_symbol),
+                    };
+
+                    if fields.contains(&field) {
+                        add_error(syn::Error::new_spanned(
+                            field,
+                            format!(
+                                "Field `{}` is bound multiple times in `{}`",
+                                field, production.rhs
+                            ),
+                        ));
+                    }
+                    fields.push(field);
+
+                    let mut types = vec![];
+                    bound_symbol.type_names(&mut types);
+
+                    for ty in types {
+                        if defined.contains(&ty)
+                            || PREDEFINED_TYPES.iter().any(|predefined| ty == predefined)
+                        {
+                            continue;
+                        }
+
+                        add_error(syn::Error::new_spanned(
+                            ty,
+                            format!(
+                                "Undefined non-terminal `{}`. Types defined outside of the grammar \
+                                should be declared with `extern {};`",
+                                ty, ty
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+
+        match errors {
+            Some(errors) => Err(errors),
+            None => Ok(()),
+        }
     }
 }
 
 impl Parse for NonTerminal {
     fn parse(input: &ParseBuffer) -> syn::Result<Self> {
+        let mut reader = None;
+
+        for attr in input.call(syn::Attribute::parse_outer)? {
+            if attr.path.is_ident("reader") && reader.is_none() {
+                reader = Some(attr.parse_args::<Reader>()?);
+            } else {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "Only a `#[reader(...)]` attribute is supported in non-terminals",
+                ));
+            }
+        }
+
         let name = syn::Ident::parse(input)?;
 
         let mut productions = Vec::new();
@@ -124,15 +347,82 @@ impl Parse for NonTerminal {
         }
 
         if productions.is_empty() {
-            return Err(input.error("Non-terminal should have at least one production"));
+            return Err(syn::Error::new_spanned(
+                name,
+                "Non-terminal should have at least one production",
+            ));
         }
 
-        Ok(NonTerminal { name, productions })
+        Ok(NonTerminal {
+            name,
+            reader,
+            productions,
+        })
+    }
+}
+
+impl Parse for Reader {
+    fn parse(input: &ParseBuffer) -> syn::Result<Self> {
+        let name = syn::Ident::parse(input)?;
+        syn::token::Comma::parse(input)?;
+        let item = syn::Ident::parse(input)?;
+
+        let mut markers = Vec::new();
+
+        while !input.is_empty() {
+            syn::token::Comma::parse(input)?;
+            if input.is_empty() {
+                break;
+            }
+            let marker = syn::Ident::parse(input)?;
+            syn::token::Eq::parse(input)?;
+            let byte = syn::LitInt::parse(input)?.base10_parse::<u8>()?;
+            markers.push((marker, byte));
+        }
+
+        Ok(Reader {
+            name,
+            item,
+            markers,
+        })
     }
 }
 
 impl Parse for Production {
     fn parse(input: &ParseBuffer) -> syn::Result<Self> {
+        let mut attrs = Vec::new();
+        let mut features = Vec::new();
+        let mut checks = Vec::new();
+
+        for attr in input.call(syn::Attribute::parse_outer)? {
+            if attr.path.is_ident("cfg") {
+                attrs.push(attr);
+            } else if attr.path.is_ident("feature") {
+                let feature = match attr.parse_meta()? {
+                    syn::Meta::NameValue(syn::MetaNameValue {
+                        lit: syn::Lit::Str(feature),
+                        ..
+                    }) => feature,
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            attr,
+                            "Feature attributes should be in form `#[feature = \"...\"]`",
+                        ))
+                    }
+                };
+                attrs.push(syn::parse_quote_spanned!(attr.span()=> #[cfg(feature = #feature)]));
+                features.push(feature);
+            } else if attr.path.is_ident("check") {
+                checks.push(attr.parse_args::<syn::Path>()?);
+            } else {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "Only `#[cfg(...)]`, `#[feature = \"...\"]` and `#[check(...)]` attributes are \
+                    supported in productions",
+                ));
+            }
+        }
+
         let mut symbols = Vec::new();
 
         while !input.peek(syn::token::Eq) {
@@ -149,36 +439,84 @@ impl Parse for Production {
 
         syn::token::Comma::parse(input)?;
 
-        Ok(Production { symbols, rhs })
+        Ok(Production {
+            attrs,
+            features,
+            checks,
+            symbols,
+            rhs,
+        })
+    }
+}
+
+impl Parse for Extern {
+    fn parse(input: &ParseBuffer) -> syn::Result<Self> {
+        syn::token::Extern::parse(input)?;
+        let name = syn::Ident::parse(input)?;
+
+        let firsts = if input.peek(syn::token::Colon) {
+            syn::token::Colon::parse(input)?;
+            let mut firsts = vec![];
+            loop {
+                let mut first = vec![];
+                while !input.peek(syn::token::Or) && !input.peek(syn::token::Semi) {
+                    match Symbol::parse(input)? {
+                        Symbol::Literal(lit) => first.push(lit),
+                        Symbol::Bound(field, _) => {
+                            return Err(syn::Error::new_spanned(
+                                field,
+                                "First bytes of an extern can only be literals",
+                            ))
+                        }
+                    }
+                }
+                if first.is_empty() {
+                    return Err(input.error("Expected a literal"));
+                }
+                firsts.push(first);
+
+                if !input.peek(syn::token::Or) {
+                    break;
+                }
+                syn::token::Or::parse(input)?;
+            }
+            Some(firsts)
+        } else {
+            None
+        };
+
+        syn::token::Semi::parse(input)?;
+        Ok(Extern { name, firsts })
     }
 }
 
 impl Parse for Symbol {
     fn parse(input: &ParseBuffer) -> syn::Result<Self> {
         if input.peek(syn::LitInt) {
-            let lit = syn::LitInt::parse(input).unwrap();
+            let lit = syn::LitInt::parse(input)?;
 
             // `0xAB` or `0xAB:u32` (or `123:u32` etc.)
             if input.peek(syn::token::Colon) {
-                syn::token::Colon::parse(input).unwrap();
-                let lit_ty = syn::Ident::parse(input)?.to_string();
-                match lit_ty.as_str() {
+                syn::token::Colon::parse(input)?;
+                let lit_ty = syn::Ident::parse(input)?;
+                match lit_ty.to_string().as_str() {
                     "i32" => Ok(Symbol::Literal(Literal::I32(lit.base10_parse()?))),
                     "u32" => Ok(Symbol::Literal(Literal::U32(lit.base10_parse()?))),
                     "i64" => Ok(Symbol::Literal(Literal::I64(lit.base10_parse()?))),
                     "u64" => Ok(Symbol::Literal(Literal::U64(lit.base10_parse()?))),
-                    _ => {
-                        Err(input.error("Literal type can be one of: i32, u32, i64, u64, f32, f64"))
-                    }
+                    _ => Err(syn::Error::new_spanned(
+                        lit_ty,
+                        "Literal type can be one of: i32, u32, i64, u64",
+                    )),
                 }
             } else {
                 let lit_value = lit.base10_parse::<u8>()?;
                 Ok(Symbol::Literal(Literal::U8(lit_value)))
             }
         } else if input.peek(syn::Ident) {
-            let ident = syn::Ident::parse(input).unwrap();
+            let ident = syn::Ident::parse(input)?;
             syn::token::Colon::parse(input)?;
-            let symbol = BoundSymbol::parse(input).unwrap();
+            let symbol = BoundSymbol::parse(input)?;
             Ok(Symbol::Bound(ident, symbol))
         } else {
             Err(input.error("Expected a u8 literal or identifier"))
@@ -188,41 +526,106 @@ impl Parse for Symbol {
 
 impl Parse for BoundSymbol {
     fn parse(input: &ParseBuffer) -> syn::Result<Self> {
+        BoundSymbol::parse_nested(input, Nesting::Field)
+    }
+}
+
+impl BoundSymbol {
+    fn parse_nested(input: &ParseBuffer, nesting: Nesting) -> syn::Result<Self> {
+        if input.peek(syn::token::Paren) {
+            let parenthesized;
+            let paren = syn::parenthesized!(parenthesized in input);
+            let elems = Punctuated::<BoundSymbol, syn::token::Comma>::parse_terminated_with(
+                &parenthesized,
+                |input| BoundSymbol::parse_nested(input, Nesting::Nested),
+            )?;
+            if elems.len() < 2 {
+                return Err(parenthesized.error("Tuples should have at least two elements"));
+            }
+            if elems.len() > MAX_TUPLE_LEN {
+                return Err(syn::Error::new(
+                    paren.span,
+                    format!("Tuples can have at most {} elements", MAX_TUPLE_LEN),
+                ));
+            }
+            return Ok(BoundSymbol::Tuple(elems.into_iter().collect()));
+        }
+
         let ident = syn::Ident::parse(input)?;
         match ident.to_string().as_str() {
-            "vec" => {
+            "vec" | "repeated" | "sized" | "optional" => {
                 let parenthesized;
                 syn::parenthesized!(parenthesized in input);
-                let type_name = syn::Ident::parse(&parenthesized)?;
+                let nested_nesting = if ident == "sized" {
+                    Nesting::Sized
+                } else {
+                    Nesting::Nested
+                };
+                let nested = Box::new(BoundSymbol::parse_nested(&parenthesized, nested_nesting)?);
                 if !parenthesized.is_empty() {
-                    return Err(parenthesized.error("Expected type name in `vec(...)`"));
+                    return Err(
+                        parenthesized.error(format!("Expected one symbol in `{}(...)`", ident))
+                    );
                 }
-                Ok(BoundSymbol::Vec(type_name))
-            }
-
-            "repeated" => {
-                let parenthesized;
-                syn::parenthesized!(parenthesized in input);
-                let type_name = syn::Ident::parse(&parenthesized)?;
-                if !parenthesized.is_empty() {
-                    return Err(parenthesized.error("Expected type name in `repeated(...)`"));
+                match ident.to_string().as_str() {
+                    "vec" => Ok(BoundSymbol::Vec(nested)),
+                    "sized" => Ok(BoundSymbol::Sized(nested)),
+                    "optional" => Ok(BoundSymbol::Optional(nested)),
+                    _ => {
+                        if nesting == Nesting::Nested {
+                            return Err(syn::Error::new_spanned(
+                                ident,
+                                "`repeated(...)` can only be used as a field, or in `sized(...)`",
+                            ));
+                        }
+                        Ok(BoundSymbol::Repeated(nested))
+                    }
                 }
-                Ok(BoundSymbol::Repeated(type_name))
             }
 
-            "sized" => {
+            "bytes" | "lanes" => {
                 let parenthesized;
                 syn::parenthesized!(parenthesized in input);
-                let sized_thing = BoundSymbol::parse(&parenthesized)?;
+                let n_lit = syn::LitInt::parse(&parenthesized)?;
                 if !parenthesized.is_empty() {
-                    return Err(parenthesized.error("Expected type in `sized(...)`"));
+                    return Err(parenthesized.error(format!("Expected a size in `{}(...)`", ident)));
+                }
+                let n = n_lit.base10_parse::<usize>()?;
+                if n == 0 {
+                    return Err(syn::Error::new_spanned(n_lit, "Size should be positive"));
+                }
+                if ident == "bytes" {
+                    Ok(BoundSymbol::Builtin(BuiltinSymbol::Bytes(n)))
+                } else if n > 128 {
+                    // Lane indices are bytes, so can index at most 256 lanes
+                    Err(syn::Error::new_spanned(
+                        n_lit,
+                        "Number of lanes can be at most 128",
+                    ))
+                } else if nesting != Nesting::Field {
+                    Err(syn::Error::new_spanned(
+                        ident,
+                        "`lanes(...)` can only be used as a field",
+                    ))
+                } else {
+                    Ok(BoundSymbol::Builtin(BuiltinSymbol::Lanes(n)))
                 }
-                Ok(BoundSymbol::Sized(Box::new(sized_thing)))
             }
 
-            "name" => Ok(BoundSymbol::Builtin(BuiltinSymbol::Name)),
+            "s33_index" if nesting != Nesting::Field => Err(syn::Error::new_spanned(
+                ident,
+                "`s33_index` can only be used as a field",
+            )),
 
+            "name" => Ok(BoundSymbol::Builtin(BuiltinSymbol::Name)),
+            "u8" => Ok(BoundSymbol::Builtin(BuiltinSymbol::U8)),
             "u32" => Ok(BoundSymbol::Builtin(BuiltinSymbol::U32)),
+            "u64" => Ok(BoundSymbol::Builtin(BuiltinSymbol::U64)),
+            "i32" => Ok(BoundSymbol::Builtin(BuiltinSymbol::I32)),
+            "i64" => Ok(BoundSymbol::Builtin(BuiltinSymbol::I64)),
+            "s33_index" => Ok(BoundSymbol::Builtin(BuiltinSymbol::S33Index)),
+            "f32" => Ok(BoundSymbol::Builtin(BuiltinSymbol::F32)),
+            "f64" => Ok(BoundSymbol::Builtin(BuiltinSymbol::F64)),
 
             _ => Ok(BoundSymbol::Type(ident)),
         }
@@ -232,9 +635,72 @@ impl Parse for BoundSymbol {
 #[test]
 fn parse_bound_symbol_vec() {
     match syn::parse_str::<BoundSymbol>("vec(A)").unwrap() {
-        BoundSymbol::Vec(ident) => assert_eq!(ident.to_string(), "A"),
+        BoundSymbol::Vec(vec) => match *vec {
+            BoundSymbol::Type(ident) => assert_eq!(ident.to_string(), "A"),
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_bound_symbol_nested() {
+    match syn::parse_str::<BoundSymbol>("vec(sized(repeated(u8)))").unwrap() {
+        BoundSymbol::Vec(vec) => match *vec {
+            BoundSymbol::Sized(sized) => match *sized {
+                BoundSymbol::Repeated(repeated) => {
+                    assert!(matches!(*repeated, BoundSymbol::Builtin(BuiltinSymbol::U8)))
+                }
+                _ => panic!(),
+            },
+            _ => panic!(),
+        },
         _ => panic!(),
     }
+
+    assert!(syn::parse_str::<BoundSymbol>("vec(repeated(A))").is_err());
+    assert!(syn::parse_str::<BoundSymbol>("optional(repeated(A))").is_err());
+    assert!(syn::parse_str::<BoundSymbol>("vec(s33_index)").is_err());
+    assert!(syn::parse_str::<BoundSymbol>("vec(A B)").is_err());
+}
+
+#[test]
+fn parse_bound_symbol_optional() {
+    match syn::parse_str::<BoundSymbol>("optional(vec(A))").unwrap() {
+        BoundSymbol::Optional(optional) => match *optional {
+            BoundSymbol::Vec(_) => (),
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn parse_bound_symbol_tuple() {
+    match syn::parse_str::<BoundSymbol>("(name, vec(A), optional(u32))").unwrap() {
+        BoundSymbol::Tuple(elems) => {
+            assert_eq!(elems.len(), 3);
+            assert!(matches!(
+                elems[0],
+                BoundSymbol::Builtin(BuiltinSymbol::Name)
+            ));
+            assert!(matches!(elems[1], BoundSymbol::Vec(_)));
+            assert!(matches!(elems[2], BoundSymbol::Optional(_)));
+        }
+        _ => panic!(),
+    }
+
+    assert!(syn::parse_str::<BoundSymbol>("(A)").is_err());
+    assert!(syn::parse_str::<BoundSymbol>("()").is_err());
+    assert!(syn::parse_str::<BoundSymbol>("(A, repeated(B))").is_err());
+
+    assert!(syn::parse_str::<BoundSymbol>("(A, B, C, D)").is_ok());
+    assert_eq!(
+        syn::parse_str::<BoundSymbol>("(A, B, C, D, E)")
+            .unwrap_err()
+            .to_string(),
+        "Tuples can have at most 4 elements"
+    );
 }
 
 #[test]
@@ -259,6 +725,28 @@ fn parse_bound_symbol_builtin() {
         BoundSymbol::Builtin(BuiltinSymbol::Name) => (),
         _ => panic!(),
     }
+
+    match syn::parse_str::<BoundSymbol>("s33_index").unwrap() {
+        BoundSymbol::Builtin(BuiltinSymbol::S33Index) => (),
+        _ => panic!(),
+    }
+
+    match syn::parse_str::<BoundSymbol>("bytes(16)").unwrap() {
+        BoundSymbol::Builtin(BuiltinSymbol::Bytes(16)) => (),
+        _ => panic!(),
+    }
+
+    match syn::parse_str::<BoundSymbol>("lanes(16)").unwrap() {
+        BoundSymbol::Builtin(BuiltinSymbol::Lanes(16)) => (),
+        _ => panic!(),
+    }
+
+    assert!(syn::parse_str::<BoundSymbol>("bytes(0)").is_err());
+    assert!(syn::parse_str::<BoundSymbol>("bytes(N)").is_err());
+    assert!(syn::parse_str::<BoundSymbol>("lanes(129)").is_err());
+    assert!(syn::parse_str::<BoundSymbol>("sized(s33_index)").is_err());
+    assert!(syn::parse_str::<BoundSymbol>("sized(lanes(16))").is_err());
+    assert!(syn::parse_str::<BoundSymbol>("sized(bytes(16))").is_ok());
 }
 
 #[test]
@@ -301,9 +789,164 @@ fn parse_symbol_literal() {
 
 #[test]
 fn parse_alternative() {
-    let Production { symbols, rhs: _ } =
-        syn::parse_str::<Production>("mod_:name import_name:name desc:ImportDesc = Import,")
+    let Production {
+        attrs,
+        features: _,
+        checks: _,
+        symbols,
+        rhs: _,
+    } = syn::parse_str::<Production>("mod_:name import_name:name desc:ImportDesc = Import,")
+        .unwrap();
+
+    assert!(attrs.is_empty());
+    assert_eq!(symbols.len(), 3);
+}
+
+#[test]
+fn parse_alternative_cfg() {
+    let Production {
+        attrs,
+        features,
+        checks,
+        symbols,
+        rhs,
+    } = syn::parse_str::<Production>("#[cfg(feature = \"simd\")] 0xFD 15:u32 = I8x16Splat,")
+        .unwrap();
+
+    assert_eq!(attrs.len(), 1);
+    assert!(features.is_empty());
+    assert!(checks.is_empty());
+    assert_eq!(symbols.len(), 2);
+    assert_eq!(rhs.to_string(), "I8x16Splat");
+
+    assert!(syn::parse_str::<Production>("#[inline] 0x00 = Nop,").is_err());
+}
+
+#[test]
+fn parse_alternative_feature() {
+    let Production {
+        attrs, features, ..
+    } = syn::parse_str::<Production>("#[feature = \"simd\"] 0xFD 15:u32 = I8x16Splat,").unwrap();
+
+    assert_eq!(attrs.len(), 1);
+    assert_eq!(features.len(), 1);
+    assert_eq!(features[0].value(), "simd");
+    assert_eq!(
+        quote::ToTokens::to_token_stream(&attrs[0]).to_string(),
+        quote::quote!(#[cfg(feature = "simd")]).to_string()
+    );
+
+    assert!(syn::parse_str::<Production>("#[feature(simd)] 0x00 = Nop,").is_err());
+}
+
+#[test]
+fn parse_alternative_check() {
+    let Production { attrs, checks, .. } =
+        syn::parse_str::<Production>("#[check(limits::check_func)] l:vec(Locals) e:Expr = Func,")
             .unwrap();
 
-    assert_eq!(symbols.len(), 3);
+    assert!(attrs.is_empty());
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0].segments.len(), 2);
+}
+
+#[test]
+fn parse_non_terminal_reader() {
+    let NonTerminal {
+        name,
+        reader,
+        productions,
+    } = syn::parse_str::<NonTerminal>(
+        "#[reader(OperatorsReader, InstrRef, End = 0x0B)] Instr { 0x01 = Nop, }",
+    )
+    .unwrap();
+
+    assert_eq!(name.to_string(), "Instr");
+    assert_eq!(productions.len(), 1);
+
+    let Reader {
+        name,
+        item,
+        markers,
+    } = reader.unwrap();
+    assert_eq!(name.to_string(), "OperatorsReader");
+    assert_eq!(item.to_string(), "InstrRef");
+    assert_eq!(markers.len(), 1);
+    assert_eq!(markers[0].0.to_string(), "End");
+    assert_eq!(markers[0].1, 0x0B);
+
+    assert!(syn::parse_str::<NonTerminal>("#[derive(Clone)] Instr { 0x01 = Nop, }").is_err());
+}
+
+#[test]
+fn parse_grammar_extern() {
+    let Grammar {
+        non_terminals,
+        externs,
+    } = syn::parse_str::<Grammar>("extern B; A { b:B = A0, bs:vec(B) = A1, }").unwrap();
+
+    assert_eq!(non_terminals.len(), 1);
+    assert_eq!(externs.len(), 1);
+    assert_eq!(externs[0].name, "B");
+    assert!(externs[0].firsts.is_none());
+
+    let Grammar {
+        non_terminals: _,
+        externs,
+    } = syn::parse_str::<Grammar>("extern B: 0x01 | 0xFD 12:u32; A { b:B = A, }").unwrap();
+
+    let firsts = externs[0].firsts.as_ref().unwrap();
+    assert_eq!(firsts.len(), 2);
+    assert!(matches!(firsts[0][..], [Literal::U8(0x01)]));
+    assert!(matches!(
+        firsts[1][..],
+        [Literal::U8(0xFD), Literal::U32(12)]
+    ));
+
+    assert!(syn::parse_str::<Grammar>("extern B:; A { b:B = A, }").is_err());
+    assert!(syn::parse_str::<Grammar>("extern B: 0x01 |; A { b:B = A, }").is_err());
+    assert!(syn::parse_str::<Grammar>("extern B: x:u8; A { b:B = A, }").is_err());
+}
+
+#[test]
+fn parse_grammar_errors() {
+    fn error(grammar: &str) -> String {
+        syn::parse_str::<Grammar>(grammar).unwrap_err().to_string()
+    }
+
+    assert_eq!(
+        error("A { b:B = A, }"),
+        "Undefined non-terminal `B`. Types defined outside of the grammar should be declared with \
+        `extern B;`"
+    );
+    assert_eq!(
+        error("A { b:sized(vec(B)) = A, }"),
+        "Undefined non-terminal `B`. Types defined outside of the grammar should be declared with \
+        `extern B;`"
+    );
+    assert_eq!(
+        error("A { 0x00 = A0, } A { 0x01 = A1, }"),
+        "`A` is defined multiple times"
+    );
+    assert_eq!(
+        error("extern A; A { 0x00 = A, }"),
+        "`A` is defined multiple times"
+    );
+    assert_eq!(
+        error("A { 0x00 = A0, 0x01 = A0, }"),
+        "Variant `A0` is defined multiple times in `A`"
+    );
+    assert_eq!(
+        error("A { 0x00 x:u32 x:u32 = A0, }"),
+        "Field `x` is bound multiple times in `A0`"
+    );
+    assert_eq!(
+        error("A { 0x00:f32 = A0, }"),
+        "Literal type can be one of: i32, u32, i64, u64"
+    );
+
+    // Malformed symbols are errors, not panics
+    assert!(syn::parse_str::<Grammar>("A { x: = A0, }").is_err());
+    assert!(syn::parse_str::<Grammar>("A { 0x00: = A0, }").is_err());
+    assert!(syn::parse_str::<Grammar>("A { 0x100 = A0, }").is_err());
 }
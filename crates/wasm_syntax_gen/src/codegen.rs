@@ -1,22 +1,34 @@
-use crate::ast::{BoundSymbol, BuiltinSymbol, Grammar, Literal, NonTerminal, Production, Symbol};
+use crate::ast::{
+    BoundSymbol, BuiltinSymbol, Grammar, Literal, NonTerminal, Production, Reader, Symbol,
+};
 
 use proc_macro2::TokenStream;
-use quote::{quote, ToTokens};
+use quote::{format_ident, quote, ToTokens};
 
 pub fn codegen(grammar: &Grammar) -> TokenStream {
     let mut impls: Vec<TokenStream> = Vec::with_capacity(grammar.non_terminals.len() * 2);
 
-    for NonTerminal { name, productions } in &grammar.non_terminals {
+    for NonTerminal {
+        name,
+        reader,
+        productions,
+    } in &grammar.non_terminals
+    {
         assert!(!productions.is_empty());
 
+        if let Err(err) = grammar.check_prefixes(name, reader.as_ref(), productions) {
+            impls.push(err.to_compile_error());
+        }
+
         // Generate the type (enum or struct)
-        let (encoder_expr, decoder_expr) = if productions.len() > 1 {
+        let ((encoder_expr, encoded_len_expr), decoder_expr) = if productions.len() > 1 {
             // enum
             let mut enum_alts: Vec<TokenStream> = Vec::with_capacity(productions.len());
             for production in productions {
+                let attrs = &production.attrs;
                 let alt_name = &production.rhs;
                 let fields = enum_fields(&production.symbols);
-                enum_alts.push(quote!(#alt_name(#fields)));
+                enum_alts.push(quote!(#(#attrs)* #alt_name(#fields)));
             }
 
             impls.push(quote!(
@@ -33,9 +45,11 @@ pub fn codegen(grammar: &Grammar) -> TokenStream {
         } else {
             // struct
             let production = &productions[0];
+            let attrs = &production.attrs;
             let fields = struct_fields(&production.symbols);
 
             impls.push(quote!(
+                #(#attrs)*
                 #[derive(Debug, PartialEq)]
                 pub struct #name(#fields);
             ));
@@ -46,25 +60,83 @@ pub fn codegen(grammar: &Grammar) -> TokenStream {
 
             (
                 generate_struct_encoder(name, &productions[0]),
-                generate_struct_decoder(&productions[0], struct_value),
+                generate_struct_decoder(grammar, &productions[0], struct_value),
             )
         };
 
+        // Recursive non-terminals are encoded with an explicit stack, shared by all non-terminals
+        // of the cycle, to avoid overflowing the stack on deeply nested values
+        let (encoder_expr, encoded_len_expr) = if grammar.is_recursive(name) {
+            let cycle = grammar.recursive_cycle(name);
+            let task_type = encode_task_type_name(cycle[0]);
+            if cycle[0] == name {
+                impls.push(generate_cycle_encoder(grammar, &cycle));
+            }
+            (
+                quote!(#task_type::#name(self).encode(writer)),
+                quote!(#task_type::#name(self).encoded_len()),
+            )
+        } else {
+            (encoder_expr, encoded_len_expr)
+        };
+
+        // Attributes of a single-production non-terminal apply to the whole type
+        let attrs: &[syn::Attribute] = if productions.len() == 1 {
+            &productions[0].attrs
+        } else {
+            &[]
+        };
+
         impls.push(quote!(
+            #(#attrs)*
             impl Encode for #name {
-                fn encode(&self, buffer: &mut Vec<u8>) {
+                fn encode_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
                     #encoder_expr
                 }
+
+                fn encoded_len(&self) -> usize {
+                    #encoded_len_expr
+                }
             }
         ));
 
+        // Recursive non-terminals count towards the nesting depth limit
+        let decoder_body = if grammar.is_recursive(name) {
+            quote!(
+                fn decode_inner<'a>(
+                    mut buffer: &'a [u8],
+                    ctx: &mut DecodeContext,
+                ) -> DecodeResult<'a, #name> {
+                    #decoder_expr
+                }
+
+                ctx.enter()?;
+                let result = decode_inner(buffer, ctx);
+                ctx.exit();
+                result
+            )
+        } else {
+            quote!(
+                let mut buffer = buffer;
+                #decoder_expr
+            )
+        };
+
         impls.push(quote!(
+            #(#attrs)*
             impl Decode for #name {
-                fn decode(mut buffer: &[u8]) -> DecodeResult<#name> {
-                    #decoder_expr
+                fn decode_with<'a>(
+                    buffer: &'a [u8],
+                    ctx: &mut DecodeContext,
+                ) -> DecodeResult<'a, #name> {
+                    #decoder_body
                 }
             }
         ));
+
+        if let Some(reader) = reader {
+            impls.push(generate_reader(grammar, name, productions, reader));
+        }
     }
 
     quote!(#(#impls)*)
@@ -80,23 +152,53 @@ fn literal_to_token_stream(lit: &Literal) -> TokenStream {
     }
 }
 
+fn format_prefix(prefix: &[u8]) -> String {
+    prefix
+        .iter()
+        .map(|byte| format!("0x{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 fn bound_symbol_type(bound_symbol: &BoundSymbol) -> TokenStream {
     match bound_symbol {
-        BoundSymbol::Vec(ty) => quote!(Vec<#ty>),
-        BoundSymbol::Repeated(ty) => quote!(Repeated<#ty>),
-        BoundSymbol::Sized(ty) => {
-            let nested_ty = bound_symbol_type(ty);
+        BoundSymbol::Vec(nested) => {
+            let nested_ty = bound_symbol_type(nested);
+            quote!(Vec<#nested_ty>)
+        }
+        BoundSymbol::Repeated(nested) => {
+            let nested_ty = bound_symbol_type(nested);
+            quote!(Repeated<#nested_ty>)
+        }
+        BoundSymbol::Sized(nested) => {
+            let nested_ty = bound_symbol_type(nested);
             quote!(Sized<#nested_ty>)
         }
+        BoundSymbol::Optional(nested) => {
+            let nested_ty = bound_symbol_type(nested);
+            quote!(Option<#nested_ty>)
+        }
+        BoundSymbol::Tuple(elems) => {
+            let elem_tys = elems.iter().map(bound_symbol_type);
+            quote!((#(#elem_tys,)*))
+        }
         BoundSymbol::Builtin(builtin) => match builtin {
             BuiltinSymbol::Name => quote!(Name),
-            BuiltinSymbol::U32 => quote!(u32),
+            BuiltinSymbol::U8 => quote!(u8),
+            BuiltinSymbol::U32 | BuiltinSymbol::S33Index => quote!(u32),
+            BuiltinSymbol::U64 => quote!(u64),
+            BuiltinSymbol::I32 => quote!(i32),
+            BuiltinSymbol::I64 => quote!(i64),
+            BuiltinSymbol::F32 => quote!(f32),
+            BuiltinSymbol::F64 => quote!(f64),
+            BuiltinSymbol::Bytes(n) | BuiltinSymbol::Lanes(n) => quote!([u8; #n]),
         },
         BoundSymbol::Type(ty) => quote!(#ty),
     }
 }
 
 impl Grammar {
+    /// Literals at the beginning of the production, encoded.
     fn production_prefix(&self, production: &Production, prefix: &mut Vec<u8>) {
         assert!(!production.symbols.is_empty());
 
@@ -104,27 +206,242 @@ impl Grammar {
             match symbol {
                 Symbol::Literal(lit) => prefix.extend_from_slice(&lit.as_u8s()),
                 Symbol::Bound(_, _) => break,
-                /*
-                Symbol::Bound(_, bound_symbol) => match bound_symbol {
-                    BoundSymbol::Type(non_terminal) => {
-                        let non_terminal = self
-                            .non_terminals
-                            .iter()
-                            .find(|nt| &nt.name == non_terminal)
-                            .unwrap();
-
-                        if non_terminal.productions.len() == 1 {
-                            self.production_prefix(&non_terminal.productions[0], prefix);
-                        }
+            }
+        }
+    }
+
+    /// Byte sequences that inputs of the production start with: the literal prefix of the
+    /// production, or when the production starts with a non-terminal of this grammar or an extern,
+    /// the first byte sequences of the type. `None` if the production can start with any byte.
+    fn production_firsts(&self, production: &Production) -> Option<Vec<Vec<u8>>> {
+        self.production_firsts_(production, &mut vec![])
+    }
+
+    /// `visiting` is the non-terminals we're computing the first bytes of, to stop at left
+    /// recursion.
+    fn production_firsts_<'a>(
+        &'a self,
+        production: &Production,
+        visiting: &mut Vec<&'a syn::Ident>,
+    ) -> Option<Vec<Vec<u8>>> {
+        let mut prefix = vec![];
+        self.production_prefix(production, &mut prefix);
+        if !prefix.is_empty() {
+            return Some(vec![prefix]);
+        }
+
+        match &production.symbols[0] {
+            Symbol::Bound(_, BoundSymbol::Type(ty)) => self.type_firsts_(ty, visiting),
+            _ => None,
+        }
+    }
+
+    /// Byte sequences that encodings of a non-terminal of the grammar, or an extern with declared
+    /// first bytes, start with. `None` if the type can start with any byte.
+    fn type_firsts(&self, ty: &syn::Ident) -> Option<Vec<Vec<u8>>> {
+        self.type_firsts_(ty, &mut vec![])
+    }
+
+    fn type_firsts_<'a>(
+        &'a self,
+        ty: &syn::Ident,
+        visiting: &mut Vec<&'a syn::Ident>,
+    ) -> Option<Vec<Vec<u8>>> {
+        if let Some(extern_) = self.externs.iter().find(|extern_| &extern_.name == ty) {
+            let firsts = extern_.firsts.as_ref()?;
+            return Some(
+                firsts
+                    .iter()
+                    .map(|first| first.iter().flat_map(Literal::as_u8s).collect())
+                    .collect(),
+            );
+        }
+
+        let non_terminal = self.non_terminals.iter().find(|nt| &nt.name == ty)?;
+
+        if visiting.contains(&&non_terminal.name) {
+            return None;
+        }
+
+        visiting.push(&non_terminal.name);
+        let mut firsts = vec![];
+        for production in &non_terminal.productions {
+            firsts.extend(self.production_firsts_(production, visiting)?);
+        }
+        visiting.pop();
+
+        Some(firsts)
+    }
+
+    /// Checks that every production of a non-terminal is reachable in the generated decoder, which
+    /// tries the productions in order by matching their literal prefixes. A production is
+    /// unreachable when a production before it, or a reader marker, matches a prefix of it.
+    /// Productions that don't start with a literal, a non-terminal of this grammar, or an extern
+    /// with declared first bytes match any input, so they can only be the last one.
+    fn check_prefixes(
+        &self,
+        name: &syn::Ident,
+        reader: Option<&Reader>,
+        productions: &[Production],
+    ) -> syn::Result<()> {
+        if productions.len() == 1 && reader.is_none() {
+            return Ok(());
+        }
+
+        let mut errors: Option<syn::Error> = None;
+        let mut add_error = |error: syn::Error| match &mut errors {
+            Some(errors) => errors.combine(error),
+            None => errors = Some(error),
+        };
+
+        // Prefixes matched so far, with a description of what they match
+        let mut matched: Vec<(Vec<u8>, String)> = vec![];
+
+        for (marker, byte) in reader.map(|reader| &reader.markers[..]).unwrap_or_default() {
+            matched.push((vec![*byte], format!("marker `{}`", marker)));
+        }
+
+        for (production_idx, production) in productions.iter().enumerate() {
+            let firsts = match self.production_firsts(production) {
+                Some(firsts) => firsts,
+                None => {
+                    if production_idx != productions.len() - 1 {
+                        add_error(syn::Error::new_spanned(
+                            &production.rhs,
+                            format!(
+                                "Production `{}::{}` can start with any byte, so the productions \
+                                after it are unreachable. Only the last production can start \
+                                without a literal, a non-terminal of this grammar, or an extern \
+                                with declared first bytes.",
+                                name, production.rhs
+                            ),
+                        ));
                     }
-                    BoundSymbol::Vec(_)
-                    | BoundSymbol::Repeated(_)
-                    | BoundSymbol::Sized(_)
-                    | BoundSymbol::Builtin(_) => break,
-                },
-                */
+                    continue;
+                }
+            };
+
+            for prefix in firsts {
+                if let Some((matched_prefix, matched_by)) = matched
+                    .iter()
+                    .find(|(matched_prefix, _)| prefix.starts_with(matched_prefix))
+                {
+                    add_error(syn::Error::new_spanned(
+                        &production.rhs,
+                        format!(
+                            "Production `{}::{}` is unreachable: its prefix [{}] is matched by {} \
+                            with prefix [{}]",
+                            name,
+                            production.rhs,
+                            format_prefix(&prefix),
+                            matched_by,
+                            format_prefix(matched_prefix),
+                        ),
+                    ));
+                }
+
+                matched.push((prefix, format!("production `{}`", production.rhs)));
             }
         }
+
+        match errors {
+            Some(errors) => Err(errors),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the non-terminal can contain itself, directly or via other non-terminals of this
+    /// grammar.
+    fn is_recursive(&self, name: &syn::Ident) -> bool {
+        self.is_reachable(name, name)
+    }
+
+    /// Non-terminals that are reachable from the recursive non-terminal `name` and can reach
+    /// `name`, in the order they are defined in the grammar. Includes `name`.
+    fn recursive_cycle(&self, name: &syn::Ident) -> Vec<&syn::Ident> {
+        let mut reachable: Vec<&syn::Ident> = vec![];
+        let mut work_list: Vec<&syn::Ident> = self.referenced_non_terminals(name);
+
+        while let Some(next) = work_list.pop() {
+            if reachable.contains(&next) {
+                continue;
+            }
+            reachable.push(next);
+            work_list.extend(self.referenced_non_terminals(next));
+        }
+
+        self.non_terminals
+            .iter()
+            .map(|nt| &nt.name)
+            .filter(|nt| reachable.contains(nt) && (*nt == name || self.is_reachable(nt, name)))
+            .collect()
+    }
+
+    /// Whether `to` is used in productions of `from`, directly or via other non-terminals.
+    fn is_reachable(&self, from: &syn::Ident, to: &syn::Ident) -> bool {
+        let mut visited: Vec<&syn::Ident> = vec![];
+        let mut work_list: Vec<&syn::Ident> = self.referenced_non_terminals(from);
+
+        while let Some(next) = work_list.pop() {
+            if next == to {
+                return true;
+            }
+            if visited.contains(&next) {
+                continue;
+            }
+            visited.push(next);
+            work_list.extend(self.referenced_non_terminals(next));
+        }
+
+        false
+    }
+
+    /// Non-terminals of this grammar used in productions of `name`.
+    fn referenced_non_terminals(&self, name: &syn::Ident) -> Vec<&syn::Ident> {
+        let non_terminal = match self.non_terminals.iter().find(|nt| &nt.name == name) {
+            Some(non_terminal) => non_terminal,
+            None => return vec![],
+        };
+
+        non_terminal
+            .productions
+            .iter()
+            .flat_map(|production| production.symbols.iter())
+            .fold(vec![], |mut types, symbol| {
+                if let Symbol::Bound(_, bound_symbol) = symbol {
+                    bound_symbol.type_names(&mut types);
+                }
+                types
+            })
+            .into_iter()
+            .filter(|ty| self.non_terminals.iter().any(|nt| &nt.name == *ty))
+            .collect()
+    }
+
+    /// Byte sequences that end a `repeated(...)` followed by `next`. An empty list means the
+    /// repetition continues until the end of the buffer.
+    fn repeated_terminators(&self, next: Option<&Symbol>) -> syn::Result<Vec<Vec<u8>>> {
+        let next = match next {
+            None => return Ok(vec![]),
+            Some(next) => next,
+        };
+
+        match next {
+            Symbol::Literal(lit) => Ok(vec![lit.as_u8s()]),
+            Symbol::Bound(name, BoundSymbol::Type(ty)) => self.type_firsts(ty).ok_or_else(|| {
+                syn::Error::new_spanned(
+                    name,
+                    "`repeated(...)` must be followed by a literal, or a type with known first \
+                     bytes: an extern declared with its first bytes, or a non-terminal of this \
+                     grammar with productions that start with literals or such types",
+                )
+            }),
+            Symbol::Bound(name, _) => Err(syn::Error::new_spanned(
+                name,
+                "`repeated(...)` must be followed by a literal, or a non-terminal of this grammar \
+                 or an extern with known first bytes",
+            )),
+        }
     }
 }
 
@@ -150,87 +467,179 @@ fn struct_fields(symbols: &[Symbol]) -> TokenStream {
     quote!(#(pub #fields,)*)
 }
 
-fn generate_enum_encoder(type_name: &syn::Ident, productions: &[Production]) -> TokenStream {
+/// Generates the bodies of `encode` and `encoded_len` of an enum.
+fn generate_enum_encoder(
+    type_name: &syn::Ident,
+    productions: &[Production],
+) -> (TokenStream, TokenStream) {
     let mut alts = Vec::with_capacity(productions.len());
+    let mut len_alts = Vec::with_capacity(productions.len());
 
     for production in productions {
         let mut instructions = Vec::with_capacity(production.symbols.len());
         let mut fields = Vec::with_capacity(production.symbols.len());
+        let mut field_lens = Vec::with_capacity(production.symbols.len());
 
         for symbol in &production.symbols {
             match symbol {
                 Symbol::Literal(lit) => {
                     let lit_tokens = literal_to_token_stream(lit);
-                    instructions.push(quote!(#lit_tokens.encode(buffer)));
+                    instructions.push(quote!(#lit_tokens.encode_to(writer)?));
                 }
-                Symbol::Bound(field_name, _) => {
+                Symbol::Bound(field_name, bound_symbol) => {
+                    let field = field_encoding_value(field_name, bound_symbol);
+                    instructions.push(quote!(#field.encode_to(writer)?));
+                    field_lens.push(quote!(#field.encoded_len()));
                     fields.push(field_name.into_token_stream());
-                    instructions.push(quote!(#field_name.encode(buffer)));
                 }
             }
         }
 
+        let attrs = &production.attrs;
         let alt_name = &production.rhs;
+        let literals_len = literals_encoded_len(&production.symbols);
 
         alts.push(quote!(
+            #(#attrs)*
             #type_name::#alt_name(#(#fields,)*) => {
                 #(#instructions;)*
+                Ok(())
+            }
+        ));
+
+        len_alts.push(quote!(
+            #(#attrs)*
+            #type_name::#alt_name(#(#fields,)*) => {
+                #literals_len #(+ #field_lens)*
             }
         ));
     }
 
-    quote!(
-        match self {
-            #(#alts)*
-        }
+    (
+        quote!(
+            match self {
+                #(#alts)*
+            }
+        ),
+        quote!(
+            match self {
+                #(#len_alts)*
+            }
+        ),
     )
 }
 
-fn generate_struct_encoder(type_name: &syn::Ident, production: &Production) -> TokenStream {
+/// Generates the bodies of `encode` and `encoded_len` of a struct.
+fn generate_struct_encoder(
+    type_name: &syn::Ident,
+    production: &Production,
+) -> (TokenStream, TokenStream) {
     let mut instructions = Vec::with_capacity(production.symbols.len());
     let mut fields = Vec::with_capacity(production.symbols.len());
+    let mut field_lens = Vec::with_capacity(production.symbols.len());
 
     for symbol in &production.symbols {
         match symbol {
             Symbol::Literal(lit) => match lit {
-                Literal::U8(u8) => instructions.push(quote!(buffer.push(#u8))),
-                Literal::U32(u32) => instructions.push(quote!(#u32.encode(buffer))),
-                Literal::I32(i32) => instructions.push(quote!(#i32.encode(buffer))),
-                Literal::U64(u64) => instructions.push(quote!(#u64.encode(buffer))),
-                Literal::I64(i64) => instructions.push(quote!(#i64.encode(buffer))),
+                Literal::U8(u8) => instructions.push(quote!(writer.write_all(&[#u8])?)),
+                Literal::U32(u32) => instructions.push(quote!(#u32.encode_to(writer)?)),
+                Literal::I32(i32) => instructions.push(quote!(#i32.encode_to(writer)?)),
+                Literal::U64(u64) => instructions.push(quote!(#u64.encode_to(writer)?)),
+                Literal::I64(i64) => instructions.push(quote!(#i64.encode_to(writer)?)),
             },
-            Symbol::Bound(field_name, _) => {
+            Symbol::Bound(field_name, bound_symbol) => {
+                let field = field_encoding_value(field_name, bound_symbol);
+                instructions.push(quote!(#field.encode_to(writer)?));
+                field_lens.push(quote!(#field.encoded_len()));
                 fields.push(field_name.into_token_stream());
-                instructions.push(quote!(#field_name.encode(buffer)));
             }
         }
     }
 
-    quote!(
-        let #type_name(#(#fields,)*) = self;
-        #(#instructions;)*
+    let literals_len = literals_encoded_len(&production.symbols);
+
+    (
+        quote!(
+            let #type_name(#(#fields,)*) = self;
+            #(#instructions;)*
+            Ok(())
+        ),
+        quote!(
+            let #type_name(#(#fields,)*) = self;
+            #literals_len #(+ #field_lens)*
+        ),
     )
 }
 
-fn generate_struct_decoder(production: &Production, value: TokenStream) -> TokenStream {
+/// The value encoded for a field of a generated type. `s33_index` fields are stored as `u32` and
+/// encoded as signed LEB-128.
+fn field_encoding_value(field_name: &syn::Ident, bound_symbol: &BoundSymbol) -> TokenStream {
+    match bound_symbol {
+        BoundSymbol::Builtin(BuiltinSymbol::S33Index) => quote!(i64::from(*#field_name)),
+        _ => field_name.into_token_stream(),
+    }
+}
+
+/// Total encoded size of the literals in `symbols`.
+fn literals_encoded_len(symbols: &[Symbol]) -> usize {
+    symbols
+        .iter()
+        .map(|symbol| match symbol {
+            Symbol::Literal(lit) => lit.as_u8s().len(),
+            Symbol::Bound(_, _) => 0,
+        })
+        .sum()
+}
+
+fn generate_struct_decoder(
+    grammar: &Grammar,
+    production: &Production,
+    value: TokenStream,
+) -> TokenStream {
     let mut instructions: Vec<TokenStream> = Vec::with_capacity(production.symbols.len());
     let mut field_names: Vec<syn::Ident> = Vec::with_capacity(production.symbols.len());
 
-    for symbol in &production.symbols {
+    for (symbol_idx, symbol) in production.symbols.iter().enumerate() {
         if let Symbol::Bound(name, _) = symbol {
             field_names.push(name.clone());
         }
 
-        instructions.push(generate_symbol_decode_instructions(symbol));
+        let next = production.symbols.get(symbol_idx + 1);
+        instructions.push(generate_symbol_decode_instructions(grammar, symbol, next));
     }
 
+    let checks = &production.checks;
+    let feature_checks = generate_feature_checks(production);
+
     quote!(
+        #feature_checks
         #(#instructions)*
-        Ok((#value, buffer))
+        let value = #value;
+        #(#checks(&value, ctx)?;)*
+        Ok((value, buffer))
     )
 }
 
-fn generate_symbol_decode_instructions(symbol: &Symbol) -> TokenStream {
+/// Generates checks for the production's features with `WasmFeatures::is_enabled` in the decode
+/// context. Feature names are the cargo feature names of the runtime crate.
+fn generate_feature_checks(production: &Production) -> TokenStream {
+    let checks = production.features.iter().map(|feature| {
+        quote!(
+            if !ctx.features.is_enabled(#feature) {
+                return Err(DecodeError::FeatureNotEnabled(#feature));
+            }
+        )
+    });
+    quote!(#(#checks)*)
+}
+
+/// `next` is the symbol following `symbol` in the production, used to find where a `repeated(...)`
+/// ends.
+fn generate_symbol_decode_instructions(
+    grammar: &Grammar,
+    symbol: &Symbol,
+    next: Option<&Symbol>,
+) -> TokenStream {
     match symbol {
         Symbol::Literal(lit) => {
             let ty = match lit {
@@ -250,33 +659,64 @@ fn generate_symbol_decode_instructions(symbol: &Symbol) -> TokenStream {
             )
         }
         Symbol::Bound(name, bound_symbol) => match bound_symbol {
-            BoundSymbol::Vec(ty) => quote!(
-                let (#name, buffer_) = Vec::<#ty>::decode(buffer)?;
-                buffer = buffer_;
-            ),
-            BoundSymbol::Repeated(ty) => quote!(
-                let (#name, buffer_) = Repeated::<#ty>::decode(buffer)?;
-                buffer = buffer_;
-            ),
-            BoundSymbol::Sized(ty) => {
-                let sized_ty = bound_symbol_type(ty);
+            BoundSymbol::Repeated(nested) => {
+                let terminators = match grammar.repeated_terminators(next) {
+                    Ok(terminators) => terminators,
+                    Err(err) => return err.to_compile_error(),
+                };
+                let nested_ty = bound_symbol_type(nested);
                 quote!(
-                    let (#name, buffer_) = Sized::<#sized_ty>::decode(buffer)?;
+                    let (#name, buffer_) =
+                        Repeated::<#nested_ty>::decode_until(
+                            buffer,
+                            &[#(&[#(#terminators),*]),*],
+                            ctx,
+                        )?;
                     buffer = buffer_;
                 )
             }
-            BoundSymbol::Builtin(builtin) => match builtin {
-                BuiltinSymbol::Name => quote!(
-                    let (#name, buffer_) = Name::decode(buffer)?;
+            BoundSymbol::Vec(_)
+            | BoundSymbol::Sized(_)
+            | BoundSymbol::Optional(_)
+            | BoundSymbol::Tuple(_) => {
+                let ty = bound_symbol_type(bound_symbol);
+                quote!(
+                    let (#name, buffer_) = <#ty>::decode_with(buffer, ctx)?;
                     buffer = buffer_;
-                ),
-                BuiltinSymbol::U32 => quote!(
-                    let (#name, buffer_) = u32::decode(buffer)?;
+                )
+            }
+            BoundSymbol::Builtin(builtin) => {
+                let ty = bound_symbol_type(bound_symbol);
+                let check = match builtin {
+                    BuiltinSymbol::S33Index => {
+                        return quote!(
+                            let (#name, buffer_) = i64::decode_with(buffer, ctx)?;
+                            // At most 5 bytes, and non-negative
+                            if buffer.len() - buffer_.len() > 5 {
+                                return Err(DecodeError::Error);
+                            }
+                            let #name = u32::try_from(#name).map_err(|_| DecodeError::Error)?;
+                            buffer = buffer_;
+                        );
+                    }
+                    BuiltinSymbol::Lanes(n) => {
+                        let n_lanes = n * 2;
+                        quote!(
+                            if #name.iter().any(|lane| usize::from(*lane) >= #n_lanes) {
+                                return Err(DecodeError::Error);
+                            }
+                        )
+                    }
+                    _ => quote!(),
+                };
+                quote!(
+                    let (#name, buffer_) = <#ty>::decode_with(buffer, ctx)?;
+                    #check
                     buffer = buffer_;
-                ),
-            },
+                )
+            }
             BoundSymbol::Type(ty) => quote!(
-                let (#name, buffer_) = #ty::decode(buffer)?;
+                let (#name, buffer_) = #ty::decode_with(buffer, ctx)?;
                 buffer = buffer_;
             ),
         },
@@ -288,40 +728,92 @@ fn generate_enum_decoder(
     type_name: &syn::Ident,
     productions: &[Production],
 ) -> TokenStream {
-    // TODO: Make sure all alternatives start with a sequence of literals
-
     let mut alts: Vec<TokenStream> = Vec::with_capacity(productions.len());
 
+    // Whether one of the productions matches any input. The `_` arm is unreachable in that case.
+    // When the production is behind a `cfg` the `_` arm is still needed, for when it's disabled.
+    let mut has_catch_all = false;
+    let mut has_cfg_catch_all = false;
+
     for production in productions {
-        let mut firsts: Vec<u8> = Vec::with_capacity(10);
-        grammar.production_prefix(production, &mut firsts);
+        let pattern = match grammar.production_firsts(production) {
+            Some(firsts) => {
+                let patterns = firsts.iter().map(|prefix| quote!([#(#prefix,)*..]));
+                quote!(#(#patterns)|*)
+            }
+            None => {
+                if production.attrs.is_empty() {
+                    has_catch_all = true;
+                } else {
+                    has_cfg_catch_all = true;
+                }
+                quote!([..])
+            }
+        };
 
-        let pattern = quote!([#(#firsts,)*..]);
+        // Bytes of the literals at the beginning of the production, skipped after matching
+        let mut literal_prefix: Vec<u8> = Vec::with_capacity(10);
+        grammar.production_prefix(production, &mut literal_prefix);
 
         let variant_name = &production.rhs;
         let field_names = production.field_names();
         let enum_value = quote!(#type_name::#variant_name(#(#field_names),*));
 
-        let n_matched_symbols = firsts.len();
+        // Literals can be encoded as multiple bytes, so the number of matched bytes is not the same
+        // as the number of matched symbols
+        let n_matched_bytes = literal_prefix.len();
+        let n_matched_symbols = production
+            .symbols
+            .iter()
+            .take_while(|symbol| matches!(symbol, Symbol::Literal(_)))
+            .count();
 
         let instructions = production
             .symbols
             .iter()
+            .enumerate()
             .skip(n_matched_symbols)
-            .map(generate_symbol_decode_instructions);
+            .map(|(symbol_idx, symbol)| {
+                let next = production.symbols.get(symbol_idx + 1);
+                generate_symbol_decode_instructions(grammar, symbol, next)
+            });
+
+        let attrs = &production.attrs;
+        let checks = &production.checks;
+        let feature_checks = generate_feature_checks(production);
 
         alts.push(quote!(
+            #(#attrs)*
             #pattern => {
-                buffer = &buffer[#n_matched_symbols..];
-                #(#instructions)*
-                Ok((#enum_value, buffer))
+                // Each production is decoded in its own function to keep the stack frames of
+                // recursive decoders small. Without this the frame has space for the locals of
+                // all productions in debug builds.
+                fn decode_production<'a>(
+                    mut buffer: &'a [u8],
+                    ctx: &mut DecodeContext,
+                ) -> DecodeResult<'a, #type_name> {
+                    #feature_checks
+                    buffer = &buffer[#n_matched_bytes..];
+                    #(#instructions)*
+                    let value = #enum_value;
+                    #(#checks(&value, ctx)?;)*
+                    Ok((value, buffer))
+                }
+                decode_production(buffer, ctx)
             }
         ));
     }
 
-    alts.push(quote!(
-        _ => Err(DecodeError::Error)
-    ));
+    if has_cfg_catch_all && !has_catch_all {
+        alts.push(quote!(
+            #[allow(unreachable_patterns)]
+            _ => Err(DecodeError::Error)
+        ));
+    } else if !has_catch_all {
+        alts.push(quote!(
+            _ => Err(DecodeError::Error)
+        ));
+    }
 
     quote!(
         match buffer {
@@ -329,3 +821,567 @@ fn generate_enum_decoder(
         }
     )
 }
+
+fn encode_task_type_name(cycle_root: &syn::Ident) -> syn::Ident {
+    format_ident!("{}EncodeTask", cycle_root)
+}
+
+/// Kinds of encoding tasks used by a cycle, other than encoding a non-terminal of the cycle.
+#[derive(Default)]
+struct EncodeTaskKinds {
+    encode: bool,
+    len: bool,
+    s33_index: bool,
+}
+
+/// Generates a type for the encoding tasks of the non-terminals in `cycle`, with methods to encode
+/// and compute encoded size of a task with an explicit stack instead of recursion.
+fn generate_cycle_encoder(grammar: &Grammar, cycle: &[&syn::Ident]) -> TokenStream {
+    let task_type = encode_task_type_name(cycle[0]);
+    let mut kinds = EncodeTaskKinds::default();
+    let mut arms: Vec<TokenStream> = Vec::with_capacity(cycle.len() + 1);
+
+    for name in cycle {
+        let non_terminal = grammar
+            .non_terminals
+            .iter()
+            .find(|nt| &nt.name == *name)
+            .unwrap();
+
+        let mut alts: Vec<TokenStream> = Vec::with_capacity(non_terminal.productions.len());
+
+        for production in &non_terminal.productions {
+            let mut fields = Vec::with_capacity(production.symbols.len());
+            let mut pushes = Vec::with_capacity(production.symbols.len());
+
+            for symbol in &production.symbols {
+                match symbol {
+                    Symbol::Literal(lit) => {
+                        kinds.encode = true;
+                        let lit_tokens = literal_to_token_stream(lit);
+                        pushes.push(quote!(stack.push(#task_type::Encode(&#lit_tokens));));
+                    }
+                    Symbol::Bound(field_name, bound_symbol) => {
+                        fields.push(field_name);
+                        pushes.push(generate_encode_task_pushes(
+                            cycle,
+                            &task_type,
+                            field_name.to_token_stream(),
+                            bound_symbol,
+                            &mut kinds,
+                        ));
+                    }
+                }
+            }
+
+            let pattern = if non_terminal.productions.len() == 1 {
+                quote!(#name(#(#fields,)*))
+            } else {
+                let alt_name = &production.rhs;
+                quote!(#name::#alt_name(#(#fields,)*))
+            };
+
+            let attrs = &production.attrs;
+
+            alts.push(quote!(
+                #(#attrs)*
+                #pattern => {
+                    #(#pushes)*
+                }
+            ));
+        }
+
+        arms.push(quote!(
+            #task_type::#name(value) => match value {
+                #(#alts)*
+            }
+        ));
+    }
+
+    let mut variants: Vec<TokenStream> =
+        cycle.iter().map(|name| quote!(#name(&'a #name))).collect();
+    let mut leaf_patterns: Vec<TokenStream> = vec![];
+    let mut encode_arms: Vec<TokenStream> = vec![];
+    let mut len_arms: Vec<TokenStream> = vec![];
+
+    if kinds.encode {
+        variants.push(quote!(Encode(&'a dyn EncodeDyn)));
+        leaf_patterns.push(quote!(#task_type::Encode(_)));
+        encode_arms.push(quote!(#task_type::Encode(value) => value.encode_dyn(writer)?));
+        len_arms.push(quote!(#task_type::Encode(value) => len += value.encoded_len_dyn()));
+    }
+
+    if kinds.len {
+        variants.push(quote!(Len(usize)));
+        leaf_patterns.push(quote!(#task_type::Len(_)));
+        encode_arms.push(quote!(
+            #task_type::Len(value) => u32::try_from(value).unwrap().encode_to(writer)?
+        ));
+        len_arms.push(quote!(
+            #task_type::Len(value) => len += u32::try_from(value).unwrap().encoded_len()
+        ));
+    }
+
+    if kinds.s33_index {
+        variants.push(quote!(S33Index(u32)));
+        leaf_patterns.push(quote!(#task_type::S33Index(_)));
+        encode_arms.push(quote!(
+            #task_type::S33Index(value) => i64::from(value).encode_to(writer)?
+        ));
+        len_arms.push(quote!(
+            #task_type::S33Index(value) => len += i64::from(value).encoded_len()
+        ));
+    }
+
+    if !leaf_patterns.is_empty() {
+        arms.push(quote!(#(#leaf_patterns)|* => unreachable!()));
+    }
+
+    quote!(
+        enum #task_type<'a> {
+            #(#variants,)*
+        }
+
+        impl<'a> #task_type<'a> {
+            /// Pushes the tasks of a non-terminal of the cycle to the stack, in order.
+            fn expand(self, stack: &mut Vec<#task_type<'a>>) {
+                match self {
+                    #(#arms,)*
+                }
+            }
+
+            fn encode<W: Write>(self, writer: &mut W) -> io::Result<()> {
+                let mut stack: Vec<#task_type<'a>> = vec![self];
+
+                while let Some(task) = stack.pop() {
+                    match task {
+                        #(#encode_arms,)*
+                        task => {
+                            // Reverse the tasks of the value to pop them in order
+                            let n_tasks = stack.len();
+                            task.expand(&mut stack);
+                            stack[n_tasks..].reverse();
+                        }
+                    }
+                }
+
+                Ok(())
+            }
+
+            fn encoded_len(self) -> usize {
+                let mut stack: Vec<#task_type<'a>> = vec![self];
+                let mut len = 0;
+
+                while let Some(task) = stack.pop() {
+                    match task {
+                        #(#len_arms,)*
+                        task => task.expand(&mut stack),
+                    }
+                }
+
+                len
+            }
+        }
+    )
+}
+
+fn generate_encode_task_pushes(
+    cycle: &[&syn::Ident],
+    task_type: &syn::Ident,
+    value: TokenStream,
+    bound_symbol: &BoundSymbol,
+    kinds: &mut EncodeTaskKinds,
+) -> TokenStream {
+    let in_cycle = |ty: &syn::Ident| cycle.contains(&ty);
+
+    if let BoundSymbol::Builtin(BuiltinSymbol::S33Index) = bound_symbol {
+        kinds.s33_index = true;
+        return quote!(stack.push(#task_type::S33Index(*#value)););
+    }
+
+    if !bound_symbol_mentions(bound_symbol, &in_cycle) {
+        kinds.encode = true;
+        return quote!(stack.push(#task_type::Encode(#value)););
+    }
+
+    match bound_symbol {
+        BoundSymbol::Type(ty) => quote!(stack.push(#task_type::#ty(#value));),
+        BoundSymbol::Repeated(nested) => {
+            let nested_pushes =
+                generate_encode_task_pushes(cycle, task_type, quote!(value), nested, kinds);
+            quote!(
+                for value in #value.0.iter() {
+                    #nested_pushes
+                }
+            )
+        }
+        BoundSymbol::Vec(nested) => {
+            kinds.len = true;
+            let nested_pushes =
+                generate_encode_task_pushes(cycle, task_type, quote!(value), nested, kinds);
+            quote!(
+                stack.push(#task_type::Len(#value.len()));
+                for value in #value.iter() {
+                    #nested_pushes
+                }
+            )
+        }
+        BoundSymbol::Sized(nested) => {
+            kinds.len = true;
+            let nested_pushes =
+                generate_encode_task_pushes(cycle, task_type, quote!((&#value.0)), nested, kinds);
+            quote!(
+                stack.push(#task_type::Len(#value.0.encoded_len()));
+                #nested_pushes
+            )
+        }
+        BoundSymbol::Optional(nested) => {
+            kinds.encode = true;
+            let nested_pushes =
+                generate_encode_task_pushes(cycle, task_type, quote!(value), nested, kinds);
+            quote!(
+                match #value {
+                    None => stack.push(#task_type::Encode(&0x00u8)),
+                    Some(value) => {
+                        stack.push(#task_type::Encode(&0x01u8));
+                        #nested_pushes
+                    }
+                }
+            )
+        }
+        BoundSymbol::Tuple(elems) => {
+            let elem_pushes: Vec<TokenStream> = elems
+                .iter()
+                .enumerate()
+                .map(|(elem_idx, elem)| {
+                    let elem_idx = syn::Index::from(elem_idx);
+                    generate_encode_task_pushes(
+                        cycle,
+                        task_type,
+                        quote!((&value.#elem_idx)),
+                        elem,
+                        kinds,
+                    )
+                })
+                .collect();
+            quote!({
+                let value = #value;
+                #(#elem_pushes)*
+            })
+        }
+        BoundSymbol::Builtin(_) => unreachable!(),
+    }
+}
+
+/// Whether the bound symbol uses a type for which `pred` holds.
+fn bound_symbol_mentions(bound_symbol: &BoundSymbol, pred: &dyn Fn(&syn::Ident) -> bool) -> bool {
+    let mut types = vec![];
+    bound_symbol.type_names(&mut types);
+    types.into_iter().any(pred)
+}
+
+/// Generates the item type and the iterator of a `#[reader(...)]` attribute. See [`Reader`].
+fn generate_reader(
+    grammar: &Grammar,
+    name: &syn::Ident,
+    productions: &[Production],
+    reader: &Reader,
+) -> TokenStream {
+    let Reader {
+        name: reader_name,
+        item,
+        markers,
+    } = reader;
+
+    // Whether the item type borrows from the buffer
+    let mut borrows = false;
+
+    let mut variants: Vec<TokenStream> = Vec::with_capacity(productions.len() + markers.len());
+    let mut alts: Vec<TokenStream> = Vec::with_capacity(productions.len() + markers.len() + 1);
+
+    for (marker, byte) in markers {
+        variants.push(quote!(#marker()));
+        alts.push(quote!(
+            [#byte, ..] => Ok((#item::#marker(), &buffer[1..]))
+        ));
+    }
+
+    for production in productions {
+        let mut firsts: Vec<u8> = Vec::with_capacity(10);
+        grammar.production_prefix(production, &mut firsts);
+        if firsts.is_empty() {
+            return syn::Error::new_spanned(
+                &production.rhs,
+                "productions of a non-terminal with a reader must start with a literal",
+            )
+            .to_compile_error();
+        }
+
+        let n_matched_bytes = firsts.len();
+        let n_matched_symbols = production
+            .symbols
+            .iter()
+            .take_while(|symbol| matches!(symbol, Symbol::Literal(_)))
+            .count();
+
+        let mut fields: Vec<&syn::Ident> = Vec::with_capacity(production.symbols.len());
+        let mut field_types: Vec<TokenStream> = Vec::with_capacity(production.symbols.len());
+        let mut instructions: Vec<TokenStream> = Vec::with_capacity(production.symbols.len());
+
+        for (symbol_idx, symbol) in production
+            .symbols
+            .iter()
+            .enumerate()
+            .skip(n_matched_symbols)
+        {
+            match symbol {
+                // The rest of the production is yielded as separate items
+                Symbol::Bound(_, BoundSymbol::Repeated(nested)) if matches!(&**nested, BoundSymbol::Type(ty) if ty == name) => {
+                    break
+                }
+                Symbol::Bound(field_name, BoundSymbol::Repeated(_) | BoundSymbol::Sized(_)) => {
+                    return syn::Error::new_spanned(
+                        field_name,
+                        "`repeated(...)` and `sized(...)` are not supported in readers",
+                    )
+                    .to_compile_error();
+                }
+                Symbol::Bound(field_name, BoundSymbol::Vec(nested)) => {
+                    let ty = bound_symbol_type(nested);
+                    borrows = true;
+                    fields.push(field_name);
+                    field_types.push(quote!(VecReader<'a, #ty>));
+                    instructions.push(quote!(
+                        let (#field_name, buffer_) = VecReader::<#ty>::read(buffer)?;
+                        buffer = buffer_;
+                    ));
+                }
+                Symbol::Bound(field_name, bound_symbol) => {
+                    fields.push(field_name);
+                    field_types.push(bound_symbol_type(bound_symbol));
+                    let next = production.symbols.get(symbol_idx + 1);
+                    instructions.push(generate_symbol_decode_instructions(grammar, symbol, next));
+                }
+                Symbol::Literal(_) => {
+                    let next = production.symbols.get(symbol_idx + 1);
+                    instructions.push(generate_symbol_decode_instructions(grammar, symbol, next));
+                }
+            }
+        }
+
+        let attrs = &production.attrs;
+        let variant_name = &production.rhs;
+        let pattern = quote!([#(#firsts,)*..]);
+        let feature_checks = generate_feature_checks(production);
+
+        variants.push(quote!(#(#attrs)* #variant_name(#(#field_types,)*)));
+
+        alts.push(quote!(
+            #(#attrs)*
+            #pattern => {
+                #feature_checks
+                let mut buffer = &buffer[#n_matched_bytes..];
+                #(#instructions)*
+                Ok((#item::#variant_name(#(#fields,)*), buffer))
+            }
+        ));
+    }
+
+    alts.push(quote!(_ => Err(DecodeError::Error)));
+
+    let (item_params, item_type) = if borrows {
+        (quote!(<'a>), quote!(#item<'a>))
+    } else {
+        (quote!(), quote!(#item))
+    };
+
+    let item_doc = format!("A single operator yielded by [`{}`].", reader_name);
+
+    quote!(
+        #[doc = #item_doc]
+        #[derive(Debug, PartialEq)]
+        pub enum #item #item_params {
+            #(#variants,)*
+        }
+
+        impl<'a> #item_type {
+            /// Decodes one operator.
+            pub fn read(buffer: &'a [u8]) -> DecodeResult<'a, #item_type> {
+                Self::read_with(buffer, &mut DecodeContext::default())
+            }
+
+            /// Decodes one operator with the given limits and enabled proposals.
+            pub fn read_with(
+                buffer: &'a [u8],
+                ctx: &mut DecodeContext,
+            ) -> DecodeResult<'a, #item_type> {
+                match buffer {
+                    #(#alts,)*
+                }
+            }
+        }
+
+        /// Iterates operators in a buffer without allocating, yielding each operator with its
+        /// offset. Iteration stops at the first error.
+        #[derive(Debug, Clone)]
+        pub struct #reader_name<'a> {
+            buffer: &'a [u8],
+            offset: usize,
+            ctx: DecodeContext,
+        }
+
+        impl<'a> #reader_name<'a> {
+            /// `offset` is the offset of `buffer` in the enclosing binary. Offsets of the
+            /// operators are relative to the same position.
+            pub fn new(buffer: &'a [u8], offset: usize) -> Self {
+                #reader_name {
+                    buffer,
+                    offset,
+                    ctx: DecodeContext::default(),
+                }
+            }
+
+            /// Decodes the operators with the given limits and enabled proposals.
+            pub fn with_context(self, ctx: DecodeContext) -> Self {
+                #reader_name { ctx, ..self }
+            }
+
+            /// Offset of the next operator.
+            pub fn offset(&self) -> usize {
+                self.offset
+            }
+        }
+
+        impl<'a> Iterator for #reader_name<'a> {
+            type Item = Result<(usize, #item_type), DecodeError>;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                if self.buffer.is_empty() {
+                    return None;
+                }
+
+                let offset = self.offset;
+                match #item::read_with(self.buffer, &mut self.ctx) {
+                    Ok((item, rest)) => {
+                        self.offset += self.buffer.len() - rest.len();
+                        self.buffer = rest;
+                        Some(Ok((offset, item)))
+                    }
+                    Err(err) => {
+                        self.buffer = &[];
+                        Some(Err(err))
+                    }
+                }
+            }
+        }
+    )
+}
+
+#[cfg(test)]
+fn check_grammar_prefixes(grammar: &str) -> Result<(), String> {
+    let grammar = syn::parse_str::<Grammar>(grammar).unwrap();
+    for non_terminal in &grammar.non_terminals {
+        grammar
+            .check_prefixes(
+                &non_terminal.name,
+                non_terminal.reader.as_ref(),
+                &non_terminal.productions,
+            )
+            .map_err(|err| err.to_string())?;
+    }
+    Ok(())
+}
+
+#[test]
+fn check_prefixes() {
+    assert_eq!(
+        check_grammar_prefixes(
+            "A { 0x00 = A0, 0x01 0x02 = A1, 0x01 0x03 = A2, x:B = A3, } B { 0x04 0x01 = B, }"
+        ),
+        Ok(())
+    );
+
+    // Longer prefixes can come before their prefixes
+    assert_eq!(
+        check_grammar_prefixes("A { 0x01 0x02 = A0, 0x01 x:u32 = A1, }"),
+        Ok(())
+    );
+
+    assert_eq!(
+        check_grammar_prefixes("A { 0x01 = A0, 0x01 0x02 = A1, }"),
+        Err(
+            "Production `A::A1` is unreachable: its prefix [0x01 0x02] is matched by production \
+            `A0` with prefix [0x01]"
+                .to_owned()
+        )
+    );
+
+    // LEB-128 literals are compared by their encodings
+    assert_eq!(
+        check_grammar_prefixes("A { 0xFD 1:u32 = A0, 0xFD 0x01 = A1, }"),
+        Err(
+            "Production `A::A1` is unreachable: its prefix [0xFD 0x01] is matched by production \
+            `A0` with prefix [0xFD 0x01]"
+                .to_owned()
+        )
+    );
+
+    assert_eq!(
+        check_grammar_prefixes("A { x:u32 = A0, 0x01 = A1, }"),
+        Err(
+            "Production `A::A0` can start with any byte, so the productions after it are \
+            unreachable. Only the last production can start without a literal, a non-terminal \
+            of this grammar, or an extern with declared first bytes."
+                .to_owned()
+        )
+    );
+
+    // First bytes of non-terminals
+    assert_eq!(
+        check_grammar_prefixes("A { b:B = A0, 0x02 = A1, } B { 0x00 = B0, 0x01 = B1, }"),
+        Ok(())
+    );
+
+    assert_eq!(
+        check_grammar_prefixes("A { b:B = A0, 0x01 = A1, } B { 0x00 = B0, 0x01 = B1, }"),
+        Err(
+            "Production `A::A1` is unreachable: its prefix [0x01] is matched by production `A0` \
+            with prefix [0x01]"
+                .to_owned()
+        )
+    );
+
+    // Declared first bytes of externs
+    assert_eq!(
+        check_grammar_prefixes("extern B: 0x00 | 0x01; A { b:B = A0, 0x02 = A1, }"),
+        Ok(())
+    );
+
+    assert_eq!(
+        check_grammar_prefixes("extern B: 0x00 | 0x01; A { b:B = A0, 0x01 0x02 = A1, }"),
+        Err(
+            "Production `A::A1` is unreachable: its prefix [0x01 0x02] is matched by production \
+            `A0` with prefix [0x01]"
+                .to_owned()
+        )
+    );
+
+    assert_eq!(
+        check_grammar_prefixes("extern B; A { b:B = A0, 0x02 = A1, }"),
+        Err(
+            "Production `A::A0` can start with any byte, so the productions after it are \
+            unreachable. Only the last production can start without a literal, a non-terminal \
+            of this grammar, or an extern with declared first bytes."
+                .to_owned()
+        )
+    );
+
+    assert_eq!(
+        check_grammar_prefixes("#[reader(AReader, AItem, End = 0x0B)] A { 0x0B 0x00 = A0, }"),
+        Err(
+            "Production `A::A0` is unreachable: its prefix [0x0B 0x00] is matched by marker \
+            `End` with prefix [0x0B]"
+                .to_owned()
+        )
+    );
+}
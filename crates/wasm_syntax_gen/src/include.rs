@@ -0,0 +1,161 @@
+//! `include_grammar!`: generates code for a grammar in a file.
+//!
+//! The compiler's lexer gives tokens parsed from a string the span of the macro call, so errors in
+//! the grammar would all point to the `include_grammar!` call. Instead the file is lexed with
+//! proc-macro2's fallback lexer, which tracks lines and columns, and locations in the file are added
+//! to the error messages. The generated code is passed to the compiler as a string.
+//!
+//! The spans are not real: errors still point to the `include_grammar!` call, and the location in
+//! the grammar file is only in the message text, as `path:line:col: message`.
+//!
+//! This depends on proc-macro2 internals: `proc_macro2::fallback::force` and `unforce` are hidden
+//! from the docs and not a stable API, and they switch a process-wide flag. proc-macro2 is pinned
+//! to an exact version in `Cargo.toml` for this reason. Line and column tracking needs the
+//! `span-locations` feature, which feature unification enables for every crate in the build that
+//! uses proc-macro2.
+
+use crate::ast::Grammar;
+use crate::codegen::codegen;
+
+use std::path::{Path, PathBuf};
+
+use proc_macro2::{Group, Literal, Span, TokenStream, TokenTree};
+use quote::{quote, ToTokens};
+
+pub fn include_grammar(path_lit: &syn::LitStr) -> syn::Result<TokenStream> {
+    let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+        .ok_or_else(|| syn::Error::new_spanned(path_lit, "`CARGO_MANIFEST_DIR` is not set"))?;
+    let path = PathBuf::from(manifest_dir).join(path_lit.value());
+
+    let source = std::fs::read_to_string(&path).map_err(|err| {
+        syn::Error::new_spanned(
+            path_lit,
+            format!("Unable to read `{}`: {}", path.display(), err),
+        )
+    })?;
+
+    let generated = {
+        let _fallback = FallbackGuard::force();
+        expand(&source, &path)
+    };
+
+    let generated: TokenStream = generated
+        .parse()
+        .map_err(|_| syn::Error::new_spanned(path_lit, "Unable to parse the generated code"))?;
+
+    let path_str = path.to_string_lossy();
+
+    Ok(quote!(
+        // Rebuild when the grammar changes
+        const _: &str = include_str!(#path_str);
+
+        #generated
+    ))
+}
+
+/// Uses proc-macro2's fallback implementation while alive. The flag is global, so tokens of the
+/// compiler should not be created or used meanwhile.
+struct FallbackGuard;
+
+impl FallbackGuard {
+    fn force() -> Self {
+        proc_macro2::fallback::force();
+        FallbackGuard
+    }
+}
+
+impl Drop for FallbackGuard {
+    fn drop(&mut self) {
+        proc_macro2::fallback::unforce();
+    }
+}
+
+/// Generates code for the grammar in `source`, read from `path`. Errors are returned as
+/// `compile_error!`s in the generated code, with locations in `path` in the messages.
+fn expand(source: &str, path: &Path) -> String {
+    let tokens: TokenStream = match source.parse() {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            let message = located_message(path, err.span(), "Unable to lex the grammar");
+            return quote!(compile_error!(#message);).to_string();
+        }
+    };
+
+    let generated = match syn::parse2::<Grammar>(tokens) {
+        Ok(grammar) => codegen(&grammar),
+        Err(err) => err.to_compile_error(),
+    };
+
+    locate_errors(generated, path).to_string()
+}
+
+fn located_message(path: &Path, span: Span, message: &str) -> String {
+    let start = span.start();
+    format!(
+        "{}:{}:{}: {}",
+        path.display(),
+        start.line,
+        start.column + 1,
+        message
+    )
+}
+
+/// Adds the locations of the errors to the messages of `compile_error!`s in `tokens`, as the spans
+/// are lost when the generated code is converted to the compiler's tokens.
+fn locate_errors(tokens: TokenStream, path: &Path) -> TokenStream {
+    let mut located: Vec<TokenTree> = vec![];
+
+    // Span of the last `compile_error` identifier, when followed by a `!`
+    let mut error_span: Option<Span> = None;
+
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) if ident == "compile_error" => {
+                error_span = Some(ident.span());
+                located.push(ident.into());
+            }
+            TokenTree::Punct(punct) if punct.as_char() == '!' => located.push(punct.into()),
+            TokenTree::Group(group) => {
+                let stream = match error_span.take() {
+                    Some(span) => match syn::parse2::<syn::LitStr>(group.stream()) {
+                        Ok(message) => {
+                            Literal::string(&located_message(path, span, &message.value()))
+                                .into_token_stream()
+                        }
+                        Err(_) => locate_errors(group.stream(), path),
+                    },
+                    None => locate_errors(group.stream(), path),
+                };
+                let mut located_group = Group::new(group.delimiter(), stream);
+                located_group.set_span(group.span());
+                located.push(located_group.into());
+            }
+            token => {
+                error_span = None;
+                located.push(token);
+            }
+        }
+    }
+
+    located.into_iter().collect()
+}
+
+#[test]
+fn expand_errors() {
+    let path = Path::new("test.grammar");
+
+    let generated = expand("A {\n    b:B = A,\n}\n", path);
+    assert!(generated.contains(
+        "test.grammar:2:7: Undefined non-terminal `B`. Types defined outside of the grammar \
+        should be declared with `extern B;`"
+    ));
+
+    let generated = expand("A {\n    0x00 = A0,\n    0x00 = A1,\n}\n", path);
+    assert!(generated.contains(
+        "test.grammar:3:12: Production `A::A1` is unreachable: its prefix [0x00] is matched by \
+        production `A0` with prefix [0x00]"
+    ));
+
+    let generated = expand("A {\n    0x00 = A0,\n", path);
+    assert!(generated.contains("test.grammar:1:3: Unable to lex the grammar"));
+}
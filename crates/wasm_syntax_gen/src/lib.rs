@@ -1,5 +1,6 @@
 mod ast;
 mod codegen;
+mod include;
 
 use ast::Grammar;
 use codegen::codegen;
@@ -11,3 +12,15 @@ pub fn grammar(input: TokenStream) -> TokenStream {
     let grammar = syn::parse_macro_input!(input as Grammar);
     codegen(&grammar).into()
 }
+
+/// Same as [`grammar!`], with the grammar read from a file. The path is relative to the crate root,
+/// as in `include_grammar!("wasm.grammar")`.
+///
+/// Errors in the grammar are reported with their locations in the file.
+#[proc_macro]
+pub fn include_grammar(input: TokenStream) -> TokenStream {
+    let path = syn::parse_macro_input!(input as syn::LitStr);
+    include::include_grammar(&path)
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}